@@ -1,38 +1,106 @@
 mod include_list;
 mod fetch_project;
 mod flattener_scripts;
+mod constant_file;
 
 use std::vec::Vec;
-use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 
 use crate::export_project as rcf;
 use rcf::fetch_project::*;
 use rcf::flattener_scripts::*;
+use rcf::constant_file::*;
+pub use rcf::flattener_scripts::{Constant, ConstantTable};
+
+pub struct ExportOptions {
+    pub user_event: Option<u8>,
+    pub user_event_path: Option<String>, // relative to the project root
+    pub constants_file: Option<String>,
+    pub skip_whitespace: bool,
+    pub skip_comments: bool,
+    pub prune_branches: bool, // removes if statements with constant conditions
+    pub inert_run: bool,
+    pub ignore_case: bool, // for matching include entries
+    pub runtime_functions: Vec<String>, // functions that constants may not call
+    pub profiles: Vec<String>, // names enabled for conditional blocks
+    pub player_receivers: Vec<String>, // accessor chains that refer to this character
+}
+
+impl ExportOptions {
+    pub fn new() -> ExportOptions {
+        ExportOptions {
+            user_event: None,
+            user_event_path: None,
+            constants_file: None,
+            skip_whitespace: false,
+            skip_comments: false,
+            prune_branches: false,
+            inert_run: false,
+            ignore_case: false,
+            runtime_functions: RUNTIME_FUNCTIONS.iter().map(|f| String::from(*f)).collect(),
+            profiles: Vec::new(),
+            player_receivers: PLAYER_RECEIVERS.iter().map(|r| String::from(*r)).collect(),
+        }
+    }
+}
+
+impl Default for ExportOptions {
+    fn default() -> ExportOptions {
+        ExportOptions::new()
+    }
+}
 
 pub fn get_export_type(src: &str) -> Option<u8> {
     get_project_type(src)
 }
 
-pub fn export_project(src: &str, dest: &str, user_event: Option<u8>, skip_whitespace: bool, skip_comments: bool, inert_run: bool) -> Result<(), String> {
-
-    let _ = fs::create_dir_all(dest); // not especially worried about errors on this one
+// Output contains any warnings raised during the export
+pub fn export_project(src: &str, dest: &str, options: &ExportOptions) -> Result<Vec<String>, String> {
 
-    let (files, ue_file) = fetch_project(src, user_event, inert_run)?;
+    let project = fetch_project(src, options.user_event, options.user_event_path.as_deref(), options.inert_run, options.ignore_case)?;
+    let (files, ue_file) = (project.files, project.user_event);
     let src = apply_trailing_slash(src);
     let dest = apply_trailing_slash(dest);
     
-    let constants_map = {
-        if let Some(ue_file) = ue_file {
-            let mut ue_path = src.clone();
-            ue_path.push_str(&ue_file);
-            let ue_script = fs::read_to_string(&ue_path).expect(&format!("Failed to read file {}", ue_path));
-            get_constants_map(&ue_script)?
-        } else {
-            HashMap::new()
+    let constants = load_constants(&src, ue_file.as_deref(), options)?;
+    let mut warnings = project.warnings;
+    warnings.extend_from_slice(constants.warnings());
+
+    // Make sure that no script modifies a constant before anything is written
+    let mut write_errors = Vec::new();
+    for f in files.iter() {
+        if Path::new(f).extension().and_then(|e| e.to_str()) != Some("gml") { continue };
+        let mut src_path = src.clone();
+        src_path.push_str(f);
+        let src_script = match fs::read_to_string(&src_path) {
+            Ok(s) => s,
+            Err(_) => return Err(format!("Failed to read file {}", src_path)),
+        };
+        write_errors.append(&mut check_constant_writes(&src_script, &constants, f, &options.profiles)?);
+        warnings.append(&mut check_shadowed_locals(&src_script, &constants, f, &options.profiles)?);
+        warnings.append(&mut check_accessor_chains(&src_script, &constants, f, options)?);
+    }
+    if !write_errors.is_empty() {
+        return Err(write_errors.join("\n"));
+    }
+
+    let _ = fs::create_dir_all(&dest); // not especially worried about errors on this one
+
+    // Constants marked with //#RCFKEEP still need to be assigned at runtime,
+    // so they're exported in a trimmed-down user_event. Calls to it are left in place.
+    let mut flatten_user_event = options.user_event;
+    if let (Some(ue_file), Some(kept_script)) = (&ue_file, make_kept_script(&constants)) {
+        let mut ue_dest = dest.clone();
+        ue_dest.push_str(ue_file);
+        if let Some(p) = Path::new(&ue_dest).parent() {
+            let _ = fs::create_dir_all(p); // not especially worried about errors on this one
         }
-    };
+        if fs::write(&ue_dest, kept_script).is_err() {
+            return Err(format!("Failed to write to file {}", ue_dest));
+        }
+        flatten_user_event = None;
+    }
 
     for f in files.iter() {
         let mut src_path = src.clone();
@@ -61,7 +129,7 @@ pub fn export_project(src: &str, dest: &str, user_event: Option<u8>, skip_whites
         if let Some(e) = extension {
             if let Some("gml") = e.to_str() {
                 let src_script = fs::read_to_string(&src_path).expect(&err1);
-                let dest_script = flatten_file(&src_script, &constants_map, user_event, skip_whitespace, skip_comments)?;
+                let dest_script = flatten_file(&src_script, &constants, f, flatten_user_event, options)?;
                 if dest_script != "" {
                     fs::write(&dest_path, &dest_script).expect(&err2);
                 }
@@ -73,8 +141,51 @@ pub fn export_project(src: &str, dest: &str, user_event: Option<u8>, skip_whites
         }
     }
 
-    Ok(())
+    Ok(warnings)
+
+}
 
+// Constants from an external file are read first, so the user_event can build on them.
+// A user_event generated from that same file only mirrors it, so it isn't read again.
+fn load_constants(src: &str, ue_file: Option<&str>, options: &ExportOptions) -> Result<ConstantTable, String> {
+    let constants_file = options.constants_file.as_deref();
+    let mut constants = ConstantTable::new();
+    if let Some(cf) = constants_file {
+        for e in read_constant_file(cf)? {
+            read_constant_value(&mut constants, &e.name, &e.value, cf, e.line, e.comment)?;
+        }
+    }
+    if let Some(ue_file) = ue_file {
+        let mut ue_path = String::from(src);
+        ue_path.push_str(ue_file);
+        let ue_script = match fs::read_to_string(&ue_path) {
+            Ok(s) => s,
+            Err(_) => return Err(format!("Failed to read file {}", ue_path)),
+        };
+        if constants_file.is_none() || !is_generated_script(&ue_script) {
+            read_constant_script(&mut constants, &ue_script, ue_file, &options.profiles)?;
+        }
+    }
+    constants.resolve()?;
+    constants.check_runtime_calls(&options.runtime_functions)?;
+    Ok(constants)
+}
+
+fn make_kept_script(constants: &ConstantTable) -> Option<String> {
+    // Kept constants may reference each other, so they're assigned in dependency order
+    let mut kept = constants.iter_resolved().filter(|c| c.keep).peekable();
+    kept.peek()?;
+
+    let mut out = String::from("// Constants kept as variables by RCF (//#RCFKEEP)\n");
+    for c in kept {
+        if let Some(comment) = &c.comment {
+            for line in comment.lines() {
+                out.push_str(&format!("// {}\n", line));
+            }
+        }
+        out.push_str(&format!("{} = {};\n", c.name, c.value));
+    }
+    Some(out)
 }
 
 // Boolean output denotes whether a new config_export.ini file was initialized
@@ -102,8 +213,55 @@ pub fn export_config(src: &str, dest: &str, inert_run: bool) -> Result<bool, Str
     }
 }
 
+// Writes a development-build user_event that mirrors an external constants file.
+// Refuses to overwrite a user_event that wasn't generated by RCF.
+pub fn generate_user_event(src: &str, user_event: u8, user_event_path: Option<&str>, constants_file: &str) -> Result<(), String> {
+    let entries = read_constant_file(constants_file)?;
+    let file_name = Path::new(constants_file).file_name()
+        .and_then(|f| f.to_str())
+        .unwrap_or(constants_file);
+
+    let mut ue_path = apply_trailing_slash(src);
+    ue_path.push_str(&get_user_event_path(user_event, user_event_path));
+    if let Ok(existing) = fs::read_to_string(&ue_path) {
+        if !is_generated_script(&existing) {
+            return Err(format!("{} was not generated by RCF and will not be overwritten", ue_path));
+        }
+    }
+
+    if let Some(p) = Path::new(&ue_path).parent() {
+        let _ = fs::create_dir_all(p); // a failure here is reported by the write below
+    }
+    match fs::write(&ue_path, make_user_event_script(&entries, file_name)) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Could not write {}: {}", ue_path, e)),
+    }
+}
+
+// Removes and then adds entries in a project's rcf_include.txt, creating it first if needed.
+// Returns the entries of the resulting include list.
+pub fn edit_include(src: &str, add: &[String], remove: &[String]) -> Result<Vec<String>, String> {
+    let project_type = match get_project_type(src) {
+        Some(t) => t,
+        None => return Err(format!("Could not find a valid project at directory {}", src)),
+    };
+    let mut incl = get_include(src, project_type, false)?;
+
+    for entry in remove.iter() {
+        incl.remove_entry(entry)?;
+    }
+    for entry in add.iter() {
+        incl.add_entry(entry)?;
+    }
+    if !add.is_empty() || !remove.is_empty() {
+        save_include(src, &incl)?;
+    }
+
+    Ok(incl.entries().iter().map(|e| String::from(*e)).collect())
+}
+
 pub fn export_project_dry(src: &str) ->  Result<(), String> {
-    fetch_project(src, None, false)?;
+    fetch_project(src, None, None, false, false)?;
     init_config_dry(src)?;
     Ok(())
 }
@@ -0,0 +1,390 @@
+use std::fs;
+use std::path::Path;
+use std::vec::Vec;
+
+use crate::export_project::flattener_scripts::is_identifier;
+
+// First line of any user_event generated by RCF.
+// Used to recognize generated scripts so that they aren't read alongside the file they mirror.
+const GENERATED_HEADER: &str = "// Generated by RCF from";
+
+pub struct ConstantEntry {
+    pub name: String,
+    pub value: String, // GML expression text
+    pub comment: Option<String>,
+    pub line: usize,
+}
+
+pub fn read_constant_file(path: &str) -> Result<Vec<ConstantEntry>, String> {
+    let contents = match fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(_) => return Err(format!("Could not read constants file {}", path)),
+    };
+    let extension = Path::new(path).extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase());
+    let entries = match extension.as_deref() {
+        Some("csv") => parse_csv(&contents),
+        Some("json") => parse_json(&contents),
+        Some("toml") => parse_toml(&contents),
+        _ => return Err(format!("Constants file {} must be a .csv, .json or .toml file", path)),
+    };
+    match entries {
+        Ok(e) => Ok(e),
+        Err(e) => Err(format!("Error in constants file {}: {}", path, e)),
+    }
+}
+
+// Produces a user_event script that assigns every entry, for use in development builds.
+pub fn make_user_event_script(entries: &[ConstantEntry], file_name: &str) -> String {
+    let mut out = format!("{} {}. Edit that file instead; changes here will be overwritten.\n\n", GENERATED_HEADER, file_name);
+    for e in entries.iter() {
+        if let Some(c) = &e.comment {
+            out.push_str(&format!("// {}\n", c));
+        }
+        out.push_str(&format!("{} = {};\n", e.name, e.value));
+    }
+    out
+}
+
+pub fn is_generated_script(script: &str) -> bool {
+    script.starts_with(GENERATED_HEADER)
+}
+
+// CSV rows take the form "name,value[,comment]".
+// A header row starting with "name" is skipped, as are blank rows and rows starting with '#'.
+fn parse_csv(contents: &str) -> Result<Vec<ConstantEntry>, String> {
+    let mut entries = Vec::new();
+    for (idx, row) in contents.lines().enumerate() {
+        let line = idx + 1;
+        let cells = split_csv_row(row, line)?;
+        let first = cells[0].trim();
+        if first.is_empty() || first.starts_with('#') { continue };
+        if line == 1 && first.eq_ignore_ascii_case("name") { continue };
+
+        let value = match cells.get(1) {
+            Some(v) if !v.trim().is_empty() => String::from(v.trim()),
+            _ => return Err(format!("Missing value for constant {} on line {}", first, line)),
+        };
+        let comment = cells.get(2)
+            .map(|c| String::from(c.trim()))
+            .filter(|c| !c.is_empty());
+        entries.push(make_entry(first, value, comment, line)?);
+    }
+    Ok(entries)
+}
+
+fn split_csv_row(row: &str, line: usize) -> Result<Vec<String>, String> {
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = row.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    cell.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if cell.trim().is_empty() => {
+                cell = String::new();
+                quoted = true;
+            }
+            ',' if !quoted => {
+                cells.push(cell);
+                cell = String::new();
+            }
+            '\r' if !quoted => (),
+            c => cell.push(c),
+        }
+    }
+
+    if quoted {
+        return Err(format!("Unterminated quoted cell on line {}", line));
+    }
+    cells.push(cell);
+    Ok(cells)
+}
+
+// JSON files must contain a single flat object.
+// Booleans are used as-is and numbers are rewritten in GML's decimal form, while strings are read as GML expressions.
+fn parse_json(contents: &str) -> Result<Vec<ConstantEntry>, String> {
+    let mut entries = Vec::new();
+    let mut p = TextCursor::new(contents);
+
+    p.skip_whitespace();
+    if !p.match_char('{') {
+        return Err(String::from("Expected '{' at start of file"));
+    }
+    p.skip_whitespace();
+    if p.match_char('}') {
+        return Ok(entries);
+    }
+
+    loop {
+        p.skip_whitespace();
+        let line = p.line;
+        if p.peek() != Some('"') {
+            return Err(format!("Expected constant name on line {}", line));
+        }
+        let name = p.json_string()?;
+        p.skip_whitespace();
+        if !p.match_char(':') {
+            return Err(format!("Expected ':' after {} on line {}", name, line));
+        }
+        p.skip_whitespace();
+        let value = match p.peek() {
+            Some('"') => p.json_string()?,
+            Some('{') | Some('[') => return Err(format!("Nested value for {} on line {} is not supported", name, line)),
+            Some(_) => {
+                let v = p.take_while(|c| !matches!(c, ',' | '}') && !c.is_whitespace());
+                if v == "null" {
+                    return Err(format!("Null value for {} on line {} is not supported", name, line));
+                }
+                match bare_value(&v) {
+                    Some(v) => v,
+                    None => return Err(format!("Invalid value '{}' for {} on line {}", v, name, line)),
+                }
+            }
+            None => return Err(format!("Unexpected end-of-file in value for {}", name)),
+        };
+        entries.push(make_entry(&name, value, None, line)?);
+
+        p.skip_whitespace();
+        if p.match_char(',') { continue };
+        if p.match_char('}') { break };
+        return Err(format!("Expected ',' or '}}' on line {}", p.line));
+    }
+
+    p.skip_whitespace();
+    if p.peek().is_some() {
+        return Err(format!("Unexpected content after closing '}}' on line {}", p.line));
+    }
+    Ok(entries)
+}
+
+// TOML files may only contain top-level "KEY = value" pairs.
+// As with JSON, strings are read as GML expressions, and anything else must be a number or boolean.
+fn parse_toml(contents: &str) -> Result<Vec<ConstantEntry>, String> {
+    let mut entries = Vec::new();
+    for (idx, row) in contents.lines().enumerate() {
+        let line = idx + 1;
+        let row = row.trim();
+        if row.is_empty() || row.starts_with('#') { continue };
+        if row.starts_with('[') {
+            return Err(format!("Tables are not supported (line {})", line));
+        }
+
+        let (name, rest) = match row.split_once('=') {
+            Some(pair) => pair,
+            None => return Err(format!("Expected '=' on line {}", line)),
+        };
+        let name = name.trim();
+        let name = name.strip_prefix('"').and_then(|n| n.strip_suffix('"')).unwrap_or(name);
+
+        let mut p = TextCursor::new(rest.trim());
+        p.line = line;
+        let value = match p.peek() {
+            Some('"') => p.json_string()?,
+            Some('\'') => {
+                p.next();
+                let v = p.take_while(|c| c != '\'');
+                if !p.match_char('\'') {
+                    return Err(format!("Unterminated string on line {}", line));
+                }
+                v
+            }
+            Some('[') | Some('{') => return Err(format!("Nested value for {} on line {} is not supported", name, line)),
+            Some(_) => {
+                let v = String::from(p.take_while(|c| c != '#').trim());
+                // TOML allows underscores between digits (e.g. 1_000)
+                let number = if v.starts_with(|c: char| c.is_ascii_digit() || matches!(c, '+' | '-' | '.')) { v.replace('_', "") } else { v.clone() };
+                match bare_value(&number) {
+                    Some(v) => v,
+                    None => return Err(format!(
+                        "Value '{}' for {} on line {} is not a number or boolean; quote it to use it as a GML expression",
+                        v, name, line,
+                    )),
+                }
+            }
+            None => return Err(format!("Missing value for constant {} on line {}", name, line)),
+        };
+        p.skip_whitespace();
+        if p.peek().is_some() && p.peek() != Some('#') {
+            return Err(format!("Unexpected content after value on line {}", line));
+        }
+        entries.push(make_entry(name, value, None, line)?);
+    }
+    Ok(entries)
+}
+
+// Reads an unquoted value, which must be a boolean or a number.
+// Numbers are written back without exponents (e.g. -2.5e3 becomes -2500), since GML has no exponent notation.
+fn bare_value(v: &str) -> Option<String> {
+    if v == "true" || v == "false" { return Some(String::from(v)) };
+    if !v.starts_with(|c: char| c.is_ascii_digit() || matches!(c, '+' | '-' | '.')) { return None };
+    match v.parse::<f64>() {
+        Ok(n) if n.is_finite() => Some(format!("{}", n)),
+        _ => None,
+    }
+}
+
+fn make_entry(name: &str, value: String, comment: Option<String>, line: usize) -> Result<ConstantEntry, String> {
+    if !is_identifier(name) {
+        return Err(format!("Invalid constant name '{}' on line {}", name, line));
+    }
+    if value.is_empty() {
+        return Err(format!("Missing value for constant {} on line {}", name, line));
+    }
+    Ok(ConstantEntry{ name: String::from(name), value, comment, line })
+}
+
+struct TextCursor {
+    source: Vec<char>,
+    current: usize,
+    line: usize,
+}
+
+impl TextCursor {
+
+    fn new(s: &str) -> TextCursor {
+        TextCursor{ source: s.chars().collect(), current: 0, line: 1 }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.source.get(self.current).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if let Some(c) = c {
+            if c == '\n' { self.line += 1 };
+            self.current += 1;
+        }
+        c
+    }
+
+    fn match_char(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !c.is_whitespace() { break };
+            self.next();
+        }
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> String {
+        let mut out = String::new();
+        while let Some(c) = self.peek() {
+            if !f(c) { break };
+            out.push(c);
+            self.next();
+        }
+        out
+    }
+
+    // Reads a double-quoted string with JSON-style escapes.
+    fn json_string(&mut self) -> Result<String, String> {
+        let start_line = self.line;
+        self.next(); // opening quote
+        let mut out = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(out),
+                Some('\\') => match self.next() {
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some('r') => out.push('\r'),
+                    Some('u') => {
+                        let code: String = (0..4).filter_map(|_| self.next()).collect();
+                        match u32::from_str_radix(&code, 16).ok().and_then(char::from_u32) {
+                            Some(c) => out.push(c),
+                            _ => return Err(format!("Invalid unicode escape on line {}", self.line)),
+                        }
+                    }
+                    Some(c) => out.push(c),
+                    None => break,
+                },
+                Some(c) => out.push(c),
+                None => break,
+            }
+        }
+        Err(format!("Unterminated string starting on line {}", start_line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(entries: &[ConstantEntry]) -> Vec<(&str, &str)> {
+        entries.iter().map(|e| (e.name.as_str(), e.value.as_str())).collect()
+    }
+
+    #[test]
+    fn csv_reads_values_and_comments() {
+        let entries = parse_csv("name,value,comment\nWALK,4,walk speed\n# skipped\n\nJUMP,\"max(1, 2)\"\n").unwrap();
+        assert_eq!(values(&entries), [("WALK", "4"), ("JUMP", "max(1, 2)")]);
+        assert_eq!(entries[0].comment.as_deref(), Some("walk speed"));
+        assert_eq!(entries[1].comment, None);
+        assert_eq!(entries[1].line, 5);
+    }
+
+    #[test]
+    fn csv_rejects_missing_values() {
+        assert!(parse_csv("WALK,\n").is_err());
+        assert!(parse_csv("1WALK,4\n").is_err());
+    }
+
+    #[test]
+    fn json_reads_flat_objects() {
+        let entries = parse_json("{\n  \"WALK\": 4,\n  \"DEBUG\": false,\n  \"RUN\": \"WALK * 2\"\n}").unwrap();
+        assert_eq!(values(&entries), [("WALK", "4"), ("DEBUG", "false"), ("RUN", "WALK * 2")]);
+        assert_eq!(entries[2].line, 4);
+        assert!(parse_json("{}").unwrap().is_empty());
+    }
+
+    #[test]
+    fn json_writes_numbers_without_exponents() {
+        let entries = parse_json("{\"A\": -2.5e3, \"B\": 1E2, \"C\": 0.125}").unwrap();
+        assert_eq!(values(&entries), [("A", "-2500"), ("B", "100"), ("C", "0.125")]);
+    }
+
+    #[test]
+    fn json_rejects_unsupported_values() {
+        assert!(parse_json("{\"A\": null}").is_err());
+        assert!(parse_json("{\"A\": [1]}").is_err());
+        assert!(parse_json("{\"A\": WALK}").is_err());
+        assert!(parse_json("{\"A\": 1} extra").is_err());
+    }
+
+    #[test]
+    fn toml_reads_pairs() {
+        let entries = parse_toml("# comment\nWALK = 4 # trailing\nRUN = \"WALK * 2\"\nNAME = 'literal'\nDEBUG = true\n").unwrap();
+        assert_eq!(values(&entries), [("WALK", "4"), ("RUN", "WALK * 2"), ("NAME", "literal"), ("DEBUG", "true")]);
+    }
+
+    #[test]
+    fn toml_strips_underscores_from_numbers_only() {
+        let entries = parse_toml("A = 1_000\nB = 1.5e-1\nC = -2_5e1\n").unwrap();
+        assert_eq!(values(&entries), [("A", "1000"), ("B", "0.15"), ("C", "-250")]);
+        let err = parse_toml("ATTACK = BASE_FRAMES * 2\n").err().unwrap();
+        assert!(err.contains("quote it"), "{}", err);
+    }
+
+    #[test]
+    fn toml_rejects_tables() {
+        assert!(parse_toml("[section]\n").is_err());
+        assert!(parse_toml("A = [1, 2]\n").is_err());
+        assert!(parse_toml("A\n").is_err());
+    }
+}
@@ -1,6 +1,7 @@
 use std::fs;
 use std::fs::File;
 use std::vec::Vec;
+use std::path::Path;
 
 use crate::export_project as rcf;
 use rcf::include_list::IncludeList;
@@ -56,20 +57,16 @@ pub fn get_project_type(root: &str) -> Option<u8> {
 pub fn get_include(root: &str, project_type: u8, inert_run: bool) -> Result<IncludeList, String> {
     if 3 < project_type { return Err(format!("Invalid project type for project in directory {}", root)) };
 
-    let mut include_path = String::from(root);
-    let len = include_path.len();
-    let end = &root[len-1..len];
-    if end != "/" && end != "\\" { include_path.push('/'); }
-    include_path.push_str("rcf_include.txt");
+    let include_path = get_include_path(root);
 
     // If include file does not exist...    
     if let Err(_) = File::open(&include_path) {
         // On inert runs: simply return the default include
         if inert_run {
-            return Ok(IncludeList::construct(&make_raw_include(project_type))?);
+            return Ok(IncludeList::construct(DEFAULT_INCLUDE, make_raw_include(project_type))?);
         }
         // On non-inert runs: create the default include in the source directory
-        let f = fs::write(&include_path, make_raw_include(project_type));
+        let f = fs::write(&include_path, DEFAULT_INCLUDE);
         if let Err(_) = f {
             return Err(format!("Could not write include file to project in directory {}", root));
         }
@@ -77,14 +74,49 @@ pub fn get_include(root: &str, project_type: u8, inert_run: bool) -> Result<Incl
 
     // Now, go ahead and read it in
     if let Ok(s) = fs::read_to_string(&include_path) {
-        Ok(IncludeList::construct(&s)?)
+        Ok(IncludeList::construct(&s, make_raw_include(project_type))?)
     } else {
         Err(format!("Could not read include file from project in directory {}", root))
     }
 
 }
 
-pub fn fetch_project(root: &str, user_event: Option<u8>, inert_run: bool) -> Result<(Vec<String>, Option<String>), String> {
+pub fn save_include(root: &str, incl: &IncludeList) -> Result<(), String> {
+    match fs::write(get_include_path(root), incl.serialize()) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(format!("Could not write include file to project in directory {}", root)),
+    }
+}
+
+fn get_include_path(root: &str) -> String {
+    let mut include_path = String::from(root);
+    let len = include_path.len();
+    let end = &root[len-1..len];
+    if end != "/" && end != "\\" { include_path.push('/'); }
+    include_path.push_str("rcf_include.txt");
+    include_path
+}
+
+// Everything found while walking the project folder.
+#[derive(Default)]
+struct ProjectScan {
+    files: Vec<String>, // files to export
+    ue_paths: Vec<String>, // files (included or not) named like the user_event, ignoring case
+    excluded: Vec<String>, // files left out of the export, and folders that weren't visited
+    seen: Vec<String>, // every file in a visited folder
+    case_mismatches: Vec<String>, // paths whose inclusion depends on letter case
+}
+
+pub struct FetchedProject {
+    pub files: Vec<String>,
+    pub user_event: Option<String>, // path to the constants source, if one was requested
+    pub warnings: Vec<String>,
+}
+
+// The configured user_event path is relative to the project root.
+// Without one, the constants source must be at scripts/user_eventN.gml.
+// Also returns warnings about the include list, such as entries that matched nothing.
+pub fn fetch_project(root: &str, user_event: Option<u8>, user_event_path: Option<&str>, inert_run: bool, ignore_case: bool) -> Result<FetchedProject, String> {
     // Ensure that valid project is being fetched
     let project_type = get_project_type(root);
     if let None = project_type {
@@ -93,73 +125,152 @@ pub fn fetch_project(root: &str, user_event: Option<u8>, inert_run: bool) -> Res
     let project_type = project_type.unwrap();
 
     // Get include list
-    let incl = Box::new(get_include(root, project_type, inert_run)?);
+    let mut incl = get_include(root, project_type, inert_run)?;
+    incl.set_ignore_case(ignore_case);
 
     // Get all files
-    if let Some(ue_num) = user_event {
-        let ue_name = format!("user_event{}.gml", ue_num);
-        match visit_folder(root, root, &ue_name, &incl) {
-            Ok((file_paths, Some(ue_path))) => Ok((file_paths, Some(ue_path))),
-            Ok((_, None)) => {
-                Err(format!("Could not locate {}", ue_name))
-            },
-            Err(_) => Err(format!("Unknown error with project at directory {}", root)),
-        }
-    } else {
-        match visit_folder(root, root, "", &incl) {
-            Ok((file_paths, _)) => Ok((file_paths, None)),
-            Err(_) => Err(format!("Unknown error with project at directory {}", root)),
+    let ue_name = match user_event {
+        Some(ue_num) => format!("user_event{}.gml", ue_num),
+        None => String::new(),
+    };
+    let mut scan = ProjectScan::default();
+    if visit_folder(root, root, &ue_name, &incl, &mut scan).is_err() {
+        return Err(format!("Unknown error with project at directory {}", root));
+    }
+
+    let ue_path = match user_event {
+        Some(ue_num) => Some(find_user_event(root, &ue_name, get_user_event_path(ue_num, user_event_path), user_event_path.is_some(), &scan.ue_paths, ignore_case)?),
+        None => None,
+    };
+
+    let mut warnings: Vec<String> = incl.unmatched_entries(&scan.seen).iter()
+        .map(|(line, entry)| format!("Include entry '{}' (line {}) did not match any files", entry, line))
+        .collect();
+
+    if let (Some(ue_num), Some(path)) = (user_event, &ue_path) {
+        let expected = get_user_event_path(ue_num, user_event_path);
+        if *path != expected {
+            warnings.push(format!("{} only matches {} when ignoring case", path, expected));
         }
     }
+    for path in scan.case_mismatches.iter() {
+        warnings.push(if ignore_case {
+            format!("{} only matches the include list when ignoring case", path)
+        } else {
+            format!("{} would match the include list if case were ignored (see -nocase)", path)
+        });
+    }
+
+    // Other copies of the user_event (such as backups) would only fail the constant write check
+    let mut file_paths = scan.files;
+    if let Some(ue_path) = &ue_path {
+        let (copies, files): (Vec<String>, Vec<String>) = file_paths.into_iter()
+            .filter(|f| f != ue_path)
+            .partition(|f| is_user_event_copy(f, &ue_name, ignore_case));
+        scan.excluded.extend(copies);
+        file_paths = files;
+    }
+
+    // Files that RCF manages itself aren't worth reporting
+    let mut excluded: Vec<&String> = scan.excluded.iter()
+        .filter(|f| Some(*f) != ue_path.as_ref() && !matches!(f.as_str(), "rcf_include.txt" | "config_export.ini"))
+        .collect();
+    excluded.sort();
+    if !excluded.is_empty() {
+        let list: Vec<String> = excluded.iter().map(|f| format!("    {}", f)).collect();
+        warnings.push(format!("The following files were not included in the export:\n{}", list.join("\n")));
+    }
 
+    Ok(FetchedProject{ files: file_paths, user_event: ue_path, warnings })
 }
 
-fn visit_folder(root: &str, cur: &str, user_event: &str, incl: &Box<IncludeList>) -> Result<(Vec<String>, Option<String>), String> {
+pub fn get_user_event_path(user_event: u8, user_event_path: Option<&str>) -> String {
+    match user_event_path {
+        Some(p) => normalize_path(p.trim_start_matches(['/', '\\'])),
+        None => format!("scripts/user_event{}.gml", user_event),
+    }
+}
+
+fn find_user_event(root: &str, ue_name: &str, expected: String, configured: bool, ue_matches: &[String], ignore_case: bool) -> Result<String, String> {
+    let same_path = |a: &str, b: &str| if ignore_case { a.to_lowercase() == b.to_lowercase() } else { a == b };
+    let copies: Vec<&String> = ue_matches.iter()
+        .filter(|p| is_user_event_copy(p, ue_name, ignore_case))
+        .collect();
+    if !configured && copies.len() > 1 {
+        let copies: Vec<&str> = copies.iter().map(|p| p.as_str()).collect();
+        return Err(format!(
+            "Found multiple copies of {} ({}); use -uepath to choose which one holds the constants (the others are left out of the export)",
+            ue_name, copies.join(", "),
+        ));
+    }
+
+    match copies.iter().find(|p| same_path(p, &expected)) {
+        Some(p) => Ok(String::from(p.as_str())),
+        None if Path::new(root).join(&expected).is_file() => Ok(expected),
+        None => match ue_matches.iter().find(|p| p.to_lowercase() == expected.to_lowercase()) {
+            Some(p) => Err(format!("Could not locate {}, though {} would match if case were ignored (see -nocase)", expected, p)),
+            None => Err(format!("Could not locate {}", expected)),
+        },
+    }
+}
+
+// Whether the file at path is named like the user_event, such as a backup in another folder.
+fn is_user_event_copy(path: &str, ue_name: &str, ignore_case: bool) -> bool {
+    let name = match Path::new(path).file_name() {
+        Some(n) => n.to_string_lossy(),
+        None => return false,
+    };
+    if ignore_case { name.to_lowercase() == ue_name.to_lowercase() } else { name == ue_name }
+}
+
+fn visit_folder(root: &str, cur: &str, user_event: &str, incl: &IncludeList, scan: &mut ProjectScan) -> Result<(), String> {
     let src_dir = fs::read_dir(cur).expect(&format!("Could not open source directory {}", cur));
-    let mut file_paths = Vec::new();
-    let mut ue_path = None;
 
-    for entry in src_dir {
-        let dir = entry.expect("Reached invalid directory entry");
+    // read_dir order varies between platforms, so entries are sorted to keep exports reproducible
+    let mut entries: Vec<fs::DirEntry> = src_dir.map(|e| e.expect("Reached invalid directory entry")).collect();
+    entries.sort_by_key(|e| e.file_name());
+
+    for dir in entries {
         let path = dir.path();
         let path_str = path.to_str().expect("Unexpected error when getting file path");
         let name = dir.file_name();
         let name = name.to_str().expect("Unexpected error when getting file name");
+        let rel_path = normalize_path(&crop_file_name(root, path_str)?);
 
         if path.is_dir() {
-            if let Some(sub_incl) = incl.get_folder(name) {
-                match visit_folder(root, path_str, user_event, sub_incl) {
-                    Ok((mut sub_paths, Some(sub_ue))) => {
-                        file_paths.append(&mut sub_paths);
-                        ue_path = Some(sub_ue);
-                    },
-                    Ok((mut sub_paths, None)) => {
-                        file_paths.append(&mut sub_paths);
-                    },
-                    Err(_) => {
-                        return Err(format!("Unknown error with project at directory {}", path_str));
-                    },
+            if incl.folder_included(&rel_path) {
+                if visit_folder(root, path_str, user_event, incl, scan).is_err() {
+                    return Err(format!("Unknown error with project at directory {}", path_str));
+                }
+            } else {
+                scan.excluded.push(format!("{}/", rel_path));
+                if incl.case_mismatch(&rel_path, true) {
+                    scan.case_mismatches.push(format!("{}/", rel_path));
                 }
             }
         }
         else if path.is_file() {
-            let mut extension = String::from(name);
-            if let Some(e) = path.extension() {
-                let e_str = e.to_str().expect("Unexpected error when getting file extension");
-                extension = String::from(".");
-                extension.push_str(e_str);
+            // Case is checked once the user_event is looked up, so that mismatches can be reported
+            if name.to_lowercase() == user_event.to_lowercase() {
+                scan.ue_paths.push(rel_path.clone());
             }
-
-            if dir.file_name().to_str() == Some(user_event) {
-                ue_path = Some(crop_file_name(root, path_str)?);
+            if incl.case_mismatch(&rel_path, false) {
+                scan.case_mismatches.push(rel_path.clone());
             }
-            else if incl.file_included(name) || incl.type_included(&extension) {
-                file_paths.push(crop_file_name(root, path_str)?);
+            if incl.file_included(&rel_path) {
+                scan.files.push(rel_path.clone());
+            } else {
+                scan.excluded.push(rel_path.clone());
             }
+            scan.seen.push(rel_path);
         }
     }
 
-    Ok((file_paths, ue_path))
+    Ok(())
+}
+
+fn normalize_path(p: &str) -> String {
+    p.replace('\\', "/")
 }
 
 fn crop_file_name(root: &str, f: &str) -> Result<String, String> {
@@ -181,6 +292,13 @@ fn crop_file_name(root: &str, f: &str) -> Result<String, String> {
     Ok(file_cropped)
 }
 
+// Written to new projects, so that they pick up changes to the default include in later versions
+const DEFAULT_INCLUDE: &str =
+"# Files and folders to export, one per line. See the RCF readme for the supported patterns.
+# @default stands in for RCF's standard include for this project type.
+@default
+";
+
 pub fn make_raw_include(project_type: u8) -> &'static str {
     match project_type {
         0 => {
@@ -3,23 +3,98 @@ mod scanner;
 mod reader;
 mod flattener;
 mod compressor;
+mod constant_table;
+mod expression;
+mod builtins;
+mod write_check;
+mod conditional;
+mod pruner;
+mod locals;
+mod accessors;
 
+use crate::export_project::ExportOptions;
 use crate::export_project::flattener_scripts as flt;
 use flt::scanner::*;
 use flt::flattener::*;
-use std::collections::HashMap;
+use flt::token::Token;
+use flt::token::Token::*;
+use flt::conditional::*;
+pub use flt::constant_table::{Constant, ConstantTable};
+pub use flt::builtins::{RUNTIME_FUNCTIONS, PLAYER_RECEIVERS};
 
-pub fn get_constants_map(src: &str) -> Result<HashMap<String, String>, String> {
+// Reads every constant assigned in a script into the table.
+// Since constants aren't known yet, conditional blocks can only check profiles here.
+pub fn read_constant_script(table: &mut ConstantTable, src: &str, source_name: &str, profiles: &[String]) -> Result<(), String> {
     let mut s = RcfScanner::new(src);
     let tokens = devectorize_errors(s.scan_tokens())?;
-    reader::get_constants_map(&tokens)
-} 
+    let tokens = resolve_conditionals(tokens, |cond| evaluate_condition(cond, &ConstantTable::new(), profiles))?;
+    for c in reader::read_constants(&tokens, source_name)? {
+        table.define(c);
+    }
+    Ok(())
+}
+
+// Reads a single constant from its value's source text.
+pub fn read_constant_value(table: &mut ConstantTable, name: &str, value: &str, source_name: &str, line: usize, comment: Option<String>) -> Result<(), String> {
+    let mut s = RcfScanner::new(value);
+    let tokens = devectorize_errors(s.scan_tokens())?;
+    let tokens = tokens.into_iter()
+        .filter(|t| !t.is_formatting())
+        .collect();
+    table.define(Constant::new(name, tokens, source_name, line, comment));
+    Ok(())
+}
+
+// Returns an error for each statement in the script that modifies a constant.
+pub fn check_constant_writes(src: &str, table: &ConstantTable, file_name: &str, profiles: &[String]) -> Result<Vec<String>, String> {
+    let tokens = scan_script(src, table, profiles)?;
+    Ok(write_check::find_constant_writes(&tokens, table, file_name))
+}
+
+// Returns a warning for each local variable or parameter that shares its name with a constant.
+pub fn check_shadowed_locals(src: &str, table: &ConstantTable, file_name: &str, profiles: &[String]) -> Result<Vec<String>, String> {
+    let tokens = scan_script(src, table, profiles)?;
+    if tokens.contains(&NoFlatten) { return Ok(Vec::new()) };
+    Ok(locals::find_shadowing_locals(&tokens, table).declarations.into_iter()
+        .map(|d| format!(
+            "{}:{}: {} {} shares its name with a constant, so it won't be flattened in this scope",
+            file_name, d.line, d.kind, d.name,
+        ))
+        .collect())
+}
+
+// Returns a warning for each constant flattened through a receiver that might not be this character.
+pub fn check_accessor_chains(src: &str, table: &ConstantTable, file_name: &str, options: &ExportOptions) -> Result<Vec<String>, String> {
+    let tokens = scan_script(src, table, &options.profiles)?;
+    Ok(accessors::find_foreign_accesses(&tokens, table, file_name, &options.player_receivers))
+}
+
+// The user_event is passed separately, since its calls are kept if any constants are kept.
+pub fn flatten_file(src: &str, table: &ConstantTable, file_name: &str, user_event: Option<u8>, options: &ExportOptions) -> Result<String, String> {
+    let tokens = scan_script(src, table, &options.profiles)?;
+    let mut f = Flattener::new(user_event, options.skip_whitespace, options.skip_comments);
+    let flattened = f.flatten_program(&tokens, table, file_name)?;
+    if !options.prune_branches { return Ok(flattened) };
+
+    // Constants have been substituted by now, so conditions on them can be evaluated
+    let mut s = RcfScanner::new(&flattened);
+    let tokens = devectorize_errors(s.scan_tokens())?;
+    Ok(pruner::prune_dead_branches(&tokens))
+}
+
+pub fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if is_alpha(c) || c == '_' => chars.all(is_identifier_char),
+        _ => false,
+    }
+}
 
-pub fn flatten_file(src: &str, map: &HashMap<String, String>, user_event: Option<u8>, skip_whitespace: bool, skip_comments: bool) -> Result<String, String> {
+// Scans a script and resolves its conditional blocks.
+fn scan_script(src: &str, table: &ConstantTable, profiles: &[String]) -> Result<Vec<Token>, String> {
     let mut s = RcfScanner::new(src);
     let tokens = devectorize_errors(s.scan_tokens())?;
-    let mut f = Flattener::new(user_event, skip_whitespace, skip_comments);
-    f.flatten_program(&tokens, map)
+    resolve_conditionals(tokens, |cond| evaluate_condition(cond, table, profiles))
 }
 
 fn devectorize_errors<T>(r: Result<T, Vec<String>>) -> Result<T, String> {
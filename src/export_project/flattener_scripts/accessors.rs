@@ -0,0 +1,105 @@
+use crate::export_project::flattener_scripts as flt;
+use flt::token::Token;
+use flt::token::Token::*;
+use flt::constant_table::ConstantTable;
+use flt::locals::find_shadowing_locals;
+use std::path::Path;
+
+// Which object a script runs as, following Rivals' file naming conventions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScriptContext {
+    Player,
+    Article,
+    Hitbox,
+}
+
+impl ScriptContext {
+    pub fn from_file_name(file_name: &str) -> ScriptContext {
+        let name = Path::new(file_name).file_name().and_then(|n| n.to_str()).unwrap_or(file_name);
+        if name.starts_with("article") { ScriptContext::Article }
+        else if name.starts_with("hitbox_") { ScriptContext::Hitbox }
+        else { ScriptContext::Player }
+    }
+
+    // Articles and hitboxes can only reach the character's variables through player_id
+    fn needs_receiver(&self) -> bool {
+        *self != ScriptContext::Player
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            ScriptContext::Player => "player",
+            ScriptContext::Article => "article",
+            ScriptContext::Hitbox => "hitbox",
+        }
+    }
+}
+
+// Finds every constant that is flattened through a receiver other than this character,
+// such as hit_player_obj.CONSTANT, returning one warning per reference.
+// Bare and self references in article and hitbox scripts are also reported, since they need player_id in development builds.
+pub fn find_foreign_accesses(ts: &[Token], table: &ConstantTable, file_name: &str, receivers: &[String]) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let context = ScriptContext::from_file_name(file_name);
+    let mut line = 1;
+    let mut ignoring = false;
+
+    let noflatten_file = ts.contains(&NoFlatten);
+    let mut noflatten = noflatten_file;
+    let shadowing = find_shadowing_locals(ts, table);
+
+    for (idx, t) in ts.iter().enumerate() {
+        line += t.line_breaks();
+        match t {
+            IgnoreBegin => ignoring = true,
+            IgnoreEnd => ignoring = false,
+            NoFlattenBegin => noflatten = true,
+            NoFlattenEnd => noflatten = noflatten_file,
+            Identifier(s) if !ignoring && !noflatten => {
+                if !table.is_flattened(s) || shadowing.shadowed.contains(&idx) { continue };
+                // In articles and hitboxes, self is the article or hitbox rather than the character
+                let receiver = get_receiver(ts, idx).filter(|r| !(r == "self" && context.needs_receiver()));
+                let receiver = match receiver {
+                    Some(r) => r,
+                    None if context.needs_receiver() => {
+                        warnings.push(format!(
+                            "{}:{}: Constant {} should be referenced as player_id.{} in {} scripts, or development builds will crash",
+                            file_name, line, s, s, context.describe(),
+                        ));
+                        continue;
+                    }
+                    None => continue,
+                };
+                if !receivers.contains(&receiver) {
+                    warnings.push(format!(
+                        "{}:{}: Constant {} is referenced through {}, which isn't known to refer to this character",
+                        file_name, line, s, receiver,
+                    ));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    warnings
+}
+
+// Reads the accessor chain before the identifier at idx (e.g. "other.player_id"), if there is one.
+// Receivers that aren't plain names, such as function calls, are described as an expression.
+pub fn get_receiver(ts: &[Token], idx: usize) -> Option<String> {
+    if idx == 0 || ts[idx-1] != Dot { return None };
+
+    let mut parts = Vec::new();
+    let mut start = idx;
+    while start >= 2 && ts[start-1] == Dot {
+        match &ts[start-2] {
+            Identifier(s) => parts.push(s.as_str()),
+            _ => return Some(String::from("an expression")),
+        }
+        start -= 2;
+    }
+    if parts.is_empty() { return Some(String::from("an expression")) };
+
+    parts.reverse();
+    Some(parts.join("."))
+}
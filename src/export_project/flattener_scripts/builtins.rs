@@ -0,0 +1,63 @@
+// Names of built-in Rivals and GameMaker variables and functions that are commonly used in character scripts.
+// Constants sharing one of these names would overwrite the built-in everywhere once flattened.
+const BUILTIN_NAMES: &[&str] = &[
+    // Instance variables
+    "x", "y", "hsp", "vsp", "state", "prev_state", "state_timer", "state_cat", "attack", "window", "window_timer",
+    "free", "spr_dir", "player", "player_id", "id", "self", "other", "hitpause", "hitstop", "hitstop_full",
+    "has_hit", "has_hit_player", "hit_player", "hit_player_obj", "was_parried", "invincible", "invince_time",
+    "perfect_dodging", "djumps", "jump_pressed", "attack_pressed", "special_pressed", "shield_pressed",
+    "taunt_pressed", "up_down", "down_down", "left_down", "right_down", "joy_dir", "joy_pad_idle",
+    "move_cooldown", "can_move", "can_jump", "can_attack", "can_special", "can_shield", "can_wall_jump",
+    "can_fast_fall", "fast_falling", "sprite_index", "image_index", "image_number", "image_speed",
+    "image_xscale", "image_yscale", "image_angle", "image_alpha", "image_blend", "mask_index", "depth",
+    "visible", "alarm", "object_index", "get_player_color", "url", "hurtbox_spr", "crouchbox_spr",
+    "air_hurtbox_spr", "hitstun", "hitstun_full", "orig_knock", "old_hsp", "old_vsp", "enemy_hitboxID",
+    "damage_scaling", "lifetime", "length", "hbox_num", "orig_player", "orig_player_id", "destroyed",
+    "in_hitpause", "strong_charge", "clone", "custom_clone", "phone_cheats",
+
+    // Character attributes
+    "char_height", "knockback_adj", "walk_speed", "walk_accel", "walk_turn_time", "initial_dash_time",
+    "initial_dash_speed", "dash_speed", "dash_turn_time", "dash_turn_accel", "dash_stop_time",
+    "dash_stop_percent", "ground_friction", "moonwalk_accel", "jump_start_time", "jump_speed",
+    "short_hop_speed", "djump_speed", "leave_ground_max", "max_jump_hsp", "air_max_speed",
+    "jump_change", "air_accel", "prat_fall_accel", "air_friction", "max_djumps", "double_jump_time",
+    "walljump_hsp", "walljump_vsp", "walljump_time", "max_fall", "fast_fall", "gravity_speed",
+    "hitstun_grav", "land_time", "prat_land_time", "wave_land_time", "wave_land_adj", "wave_friction",
+    "crouch_startup_frames", "crouch_active_frames", "crouch_recovery_frames", "dodge_startup_frames",
+    "dodge_active_frames", "dodge_recovery_frames", "tech_active_frames", "tech_recovery_frames",
+    "techroll_startup_frames", "techroll_active_frames", "techroll_recovery_frames", "techroll_speed",
+    "air_dodge_startup_frames", "air_dodge_active_frames", "air_dodge_recovery_frames", "air_dodge_speed",
+    "roll_forward_startup_frames", "roll_forward_active_frames", "roll_forward_recovery_frames",
+    "roll_back_startup_frames", "roll_back_active_frames", "roll_back_recovery_frames",
+    "roll_forward_max", "roll_backward_max", "land_sound", "landing_lag_sound", "waveland_sound",
+    "jump_sound", "djump_sound", "air_dodge_sound", "bubble_x", "bubble_y",
+
+    // Functions
+    "set_attack_value", "set_window_value", "set_hitbox_value", "set_num_hitboxes", "get_attack_value",
+    "get_window_value", "get_hitbox_value", "reset_attack_value", "reset_window_value",
+    "reset_hitbox_value", "create_hitbox", "spawn_hit_fx", "hit_fx_create", "sprite_get", "sound_get",
+    "sound_play", "sound_stop", "random_func", "random_func_2", "instance_create", "instance_destroy",
+    "instance_exists", "set_state", "set_attack", "destroy_hitboxes", "attack_end", "print",
+    "print_debug", "user_event", "get_gameplay_time", "get_player_damage", "set_player_damage",
+    "take_damage", "get_state_name", "get_char_info", "get_color_profile_slot_r", "set_color_profile_slot",
+    "set_article_color_slot", "init_shader", "shader_start", "shader_end", "draw_sprite_ext",
+    "draw_debug_text", "clamp", "lerp", "sign", "abs", "floor", "ceil", "round", "min", "max",
+    "point_distance", "point_direction", "lengthdir_x", "lengthdir_y", "string", "real", "array_length_1d",
+];
+
+pub fn is_builtin(name: &str) -> bool {
+    BUILTIN_NAMES.contains(&name)
+}
+
+// Functions whose results are only known at runtime, or that have side effects.
+// Constants calling these would re-run the call at every usage site once flattened.
+pub const RUNTIME_FUNCTIONS: &[&str] = &[
+    "hit_fx_create", "sprite_get", "sound_get", "random_func", "random_func_2", "instance_create",
+    "create_hitbox", "spawn_hit_fx", "sound_play", "get_gameplay_time", "get_player_damage",
+    "instance_exists", "instance_find", "instance_number", "irandom", "random", "irandom_range",
+    "random_range", "choose", "get_timer", "ds_list_create", "ds_map_create",
+    "ds_grid_create", "array_create",
+];
+
+// Accessor chains that refer back to the character that owns the constants.
+pub const PLAYER_RECEIVERS: &[&str] = &["self", "player_id", "other.player_id"];
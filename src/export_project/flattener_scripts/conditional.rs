@@ -0,0 +1,146 @@
+use crate::export_project::flattener_scripts as flt;
+use flt::token::Token;
+use flt::token::Token::*;
+use flt::scanner::RcfScanner;
+use flt::constant_table::ConstantTable;
+use flt::expression::evaluate;
+
+struct Branch {
+    active: bool, // whether this branch's contents are kept
+    parent_active: bool,
+    seen_else: bool,
+}
+
+// Replaces conditional blocks with ignore regions, based on whether their conditions hold.
+// Inactive branches keep their tokens (minus directives) so that line numbers are preserved.
+// Ignore regions don't nest, so no markers are added inside one that the script already has.
+pub fn resolve_conditionals(ts: Vec<Token>, is_active: impl Fn(&str) -> Result<bool, String>) -> Result<Vec<Token>, String> {
+    let mut out = Vec::new();
+    let mut branches: Vec<Branch> = Vec::new();
+    let mut line = 1;
+    let mut ignoring = false;
+
+    for t in ts {
+        line += t.line_breaks();
+        let emitting = branches.last().is_none_or(|b| b.active);
+        match t {
+            IfBegin(cond) => {
+                let active = emitting && match is_active(&cond) {
+                    Ok(a) => a,
+                    Err(e) => return Err(format!("{} (line {})", e, line)),
+                };
+                if emitting && !active && !ignoring { out.push(IgnoreBegin) };
+                branches.push(Branch{ active, parent_active: emitting, seen_else: false });
+            }
+            Else => {
+                let b = match branches.last_mut() {
+                    Some(b) if !b.seen_else => b,
+                    Some(_) => return Err(format!("Reached second //#RCFELSE in the same //#RCFIF on line {}", line)),
+                    None => return Err(format!("Reached //#RCFELSE with no matching //#RCFIF on line {}", line)),
+                };
+                b.seen_else = true;
+                if b.parent_active {
+                    if !ignoring { out.push(if b.active { IgnoreBegin } else { IgnoreEnd }) };
+                    b.active = !b.active;
+                }
+            }
+            IfEnd => {
+                match branches.pop() {
+                    Some(b) => if b.parent_active && !b.active && !ignoring { out.push(IgnoreEnd) },
+                    None => return Err(format!("Reached //#RCFENDIF with no matching //#RCFIF on line {}", line)),
+                }
+            }
+            t => {
+                if emitting {
+                    if t == IgnoreBegin { ignoring = true };
+                    if t == IgnoreEnd { ignoring = false };
+                }
+                if emitting || !is_rcf_directive(&t) { out.push(t) };
+            }
+        }
+    }
+
+    if !branches.is_empty() {
+        return Err(String::from("Reached end-of-file inside //#RCFIF block"));
+    }
+    Ok(out)
+}
+
+// Evaluates a condition at export time.
+// Constants are replaced with their values, while any other name is treated as a profile,
+// which is true only if it was enabled for this export.
+pub fn evaluate_condition(cond: &str, table: &ConstantTable, profiles: &[String]) -> Result<bool, String> {
+    let err = || format!("Could not evaluate condition '{}' at export time", cond);
+
+    let mut s = RcfScanner::new(cond);
+    let tokens = s.scan_tokens().map_err(|_| err())?;
+    let mut resolved = Vec::new();
+    for t in tokens {
+        match t {
+            Identifier(s) if profiles.contains(&s) => resolved.push(Literal(String::from("true"))),
+            Identifier(s) if table.get(&s).is_some_and(|c| c.keep) => {
+                return Err(format!("Condition '{}' uses {}, which is kept as a variable (//#RCFKEEP) and has no value at export time", cond, s));
+            }
+            Identifier(s) if table.is_flattened(&s) => {
+                let value = table.get_substitution(&s).unwrap_or_default();
+                let mut vs = RcfScanner::new(value);
+                resolved.append(&mut vs.scan_tokens().map_err(|_| err())?);
+            }
+            t @ Identifier(_) if !t.is_word_operator() => resolved.push(Literal(String::from("false"))),
+            Whitespace(_) => (),
+            t => resolved.push(t),
+        }
+    }
+
+    match evaluate(&resolved).and_then(|v| v.is_truthy()) {
+        Some(b) => Ok(b),
+        None => Err(err()),
+    }
+}
+
+// Directives inside inactive branches are dropped, so that they can't break up the ignore region.
+fn is_rcf_directive(t: &Token) -> bool {
+    matches!(t, IgnoreBegin | IgnoreEnd | DeformatBegin | DeformatEnd | Keep | NoFlatten | NoFlattenBegin | NoFlattenEnd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flt::read_constant_script;
+
+    fn resolve(src: &str, profiles: &[&str]) -> Result<String, String> {
+        let profiles: Vec<String> = profiles.iter().map(|p| String::from(*p)).collect();
+        let mut table = ConstantTable::new();
+        read_constant_script(&mut table, "DEBUG = false;\n//#RCFKEEP\nTUNING = 2;", "user_event3.gml", &[])?;
+        table.resolve()?;
+        let mut s = RcfScanner::new(src);
+        let tokens = resolve_conditionals(s.scan_tokens().unwrap(), |cond| evaluate_condition(cond, &table, &profiles))?;
+        Ok(tokens.iter().map(|t| t.to_source()).collect())
+    }
+
+    #[test]
+    fn ignores_inactive_branches() {
+        assert_eq!(resolve("//#RCFIF DEBUG\na = 1;\n//#RCFELSE\nb = 2;\n//#RCFENDIF", &[]).unwrap(),
+            "//#RCFBEGINIGNORE\na = 1;\n//#RCFENDIGNORE\nb = 2;\n");
+        assert_eq!(resolve("//#RCFIF release\na = 1;\n//#RCFENDIF", &["release"]).unwrap(), "\na = 1;\n");
+    }
+
+    #[test]
+    fn adds_no_markers_inside_ignore_regions() {
+        let src = "//#RCFBEGINIGNORE\n//#RCFIF DEBUG\na = 1;\n//#RCFELSE\nb = 2;\n//#RCFENDIF\n//#RCFENDIGNORE\nc = 3;";
+        assert_eq!(resolve(src, &[]).unwrap(), "//#RCFBEGINIGNORE\n\na = 1;\n\nb = 2;\n\n//#RCFENDIGNORE\nc = 3;");
+    }
+
+    #[test]
+    fn rejects_kept_constants_in_conditions() {
+        let err = resolve("//#RCFIF TUNING > 1\na = 1;\n//#RCFENDIF", &[]).err().unwrap();
+        assert!(err.contains("TUNING"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_unbalanced_blocks() {
+        assert!(resolve("//#RCFIF DEBUG\na = 1;", &[]).is_err());
+        assert!(resolve("//#RCFENDIF", &[]).is_err());
+        assert!(resolve("//#RCFIF DEBUG\n//#RCFELSE\n//#RCFELSE\n//#RCFENDIF", &[]).is_err());
+    }
+}
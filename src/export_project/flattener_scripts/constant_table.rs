@@ -0,0 +1,350 @@
+use crate::export_project::flattener_scripts as flt;
+use flt::token::Token;
+use flt::token::Token::*;
+use flt::expression::{fold_expression, is_atomic};
+use flt::builtins::is_builtin;
+use std::collections::HashMap;
+use std::vec::Vec;
+
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Constant {
+    pub name: String,
+    pub raw_value: String, // value as written, minus whitespace and comments
+    pub value: String, // value with references to other constants resolved, folded where possible
+    pub source: String,
+    pub line: usize,
+    pub comment: Option<String>,
+    pub dependencies: Vec<String>,
+    pub keep: bool, // kept as a real variable rather than being flattened
+    substitution: String, // value as inserted at usage sites
+    tokens: Vec<Token>,
+}
+
+impl Constant {
+
+    // Tokens should only contain the value's significant tokens (no whitespace or comments).
+    pub fn new(name: &str, tokens: Vec<Token>, source: &str, line: usize, comment: Option<String>) -> Constant {
+        let raw_value = join_tokens(&tokens, |_| None);
+        Constant {
+            name: String::from(name),
+            raw_value,
+            value: String::new(),
+            source: String::from(source),
+            line,
+            comment,
+            dependencies: Vec::new(),
+            keep: false,
+            substitution: String::new(),
+            tokens,
+        }
+    }
+
+    pub fn location(&self) -> String {
+        format!("{}:{}", self.source, self.line)
+    }
+
+    // Compound values are parenthesized, so that substituting them can't change operator precedence.
+    pub fn substitution(&self) -> &str {
+        &self.substitution
+    }
+
+}
+
+#[derive(Clone)]
+enum VisitState {
+    Unvisited,
+    Visiting,
+    Done,
+}
+
+#[derive(Default)]
+pub struct ConstantTable {
+    constants: Vec<Constant>, // every definition, including those later redefined
+    index: HashMap<String, Vec<usize>>, // definitions of each name, in definition order
+    order: Vec<usize>, // dependencies before the constants that use them, filled in by resolve()
+    warnings: Vec<String>,
+}
+
+impl ConstantTable {
+
+    pub fn new() -> ConstantTable {
+        ConstantTable{ constants: Vec::new(), index: HashMap::new(), order: Vec::new(), warnings: Vec::new() }
+    }
+
+    // Adds the constant to the table. Values are only filled in once resolve() is called.
+    // Redefining a constant doesn't discard the earlier definition, since constants defined in between may use it.
+    pub fn define(&mut self, c: Constant) {
+        if is_builtin(&c.name) {
+            self.warnings.push(format!(
+                "Constant {} (at {}) shares its name with a built-in variable or function, which flattening would overwrite",
+                c.name, c.location(),
+            ));
+        }
+        self.index.entry(c.name.clone()).or_default().push(self.constants.len());
+        self.constants.push(c);
+    }
+
+    // Resolves and folds every constant, visiting dependencies before the constants that use them.
+    // References see the definition in effect at that point (e.g. SPEED = SPEED + 1 builds on the earlier SPEED),
+    // while references to constants defined further down use their last definition.
+    pub fn resolve(&mut self) -> Result<(), String> {
+        let mut order = Vec::new();
+        let mut states = vec![VisitState::Unvisited; self.constants.len()];
+        let mut path = Vec::new();
+        for idx in 0..self.constants.len() {
+            self.visit(idx, &mut states, &mut path, &mut order)?;
+        }
+        self.check_redefinitions();
+
+        for &idx in order.iter() {
+            let c = &self.constants[idx];
+            let mut dependencies = Vec::new();
+            let mut value = join_tokens(&c.tokens, |s| {
+                let dep = &self.constants[self.reference(idx, s)?];
+                if dep.keep { return None };
+                if !dependencies.iter().any(|d| d == s) {
+                    dependencies.push(String::from(s));
+                }
+                Some(dep.substitution())
+            });
+            if let Some(folded) = fold_expression(&value) {
+                value = folded;
+            }
+            let substitution = {
+                if is_atomic(&value) { value.clone() }
+                else { format!("({})", value) }
+            };
+
+            let c = &mut self.constants[idx];
+            c.value = value;
+            c.substitution = substitution;
+            c.dependencies = dependencies;
+        }
+        self.order = order;
+        Ok(())
+    }
+
+    // Depth-first search for a topological order, failing on the first cycle found.
+    fn visit(&self, idx: usize, states: &mut Vec<VisitState>, path: &mut Vec<usize>, order: &mut Vec<usize>) -> Result<(), String> {
+        match states[idx] {
+            VisitState::Done => return Ok(()),
+            VisitState::Visiting => {
+                let start = path.iter().position(|&p| p == idx).unwrap_or(0);
+                let mut chain: Vec<&str> = path[start..].iter().map(|&p| self.constants[p].name.as_str()).collect();
+                chain.push(&self.constants[idx].name);
+                return Err(format!(
+                    "Constant {} (at {}) depends on itself: {}",
+                    self.constants[idx].name, self.constants[idx].location(), chain.join(" -> "),
+                ));
+            }
+            VisitState::Unvisited => (),
+        }
+
+        states[idx] = VisitState::Visiting;
+        path.push(idx);
+        for t in self.constants[idx].tokens.iter() {
+            if let Identifier(s) = t {
+                if let Some(dep) = self.reference(idx, s) {
+                    self.visit(dep, states, path, order)?;
+                }
+            }
+        }
+        path.pop();
+        states[idx] = VisitState::Done;
+        order.push(idx);
+        Ok(())
+    }
+
+    // Warns about definitions that are replaced before anything references them.
+    fn check_redefinitions(&mut self) {
+        let mut referenced = vec![false; self.constants.len()];
+        for (idx, c) in self.constants.iter().enumerate() {
+            for t in c.tokens.iter() {
+                if let Some(dep) = match t { Identifier(s) => self.reference(idx, s), _ => None } {
+                    referenced[dep] = true;
+                }
+            }
+        }
+
+        for (idx, c) in self.constants.iter().enumerate() {
+            let defs = &self.index[&c.name];
+            let next = match defs.iter().find(|&&d| d > idx) {
+                Some(&next) => next,
+                None => continue,
+            };
+            if !referenced[idx] {
+                self.warnings.push(format!(
+                    "Constant {} is defined at both {} and {}; the first definition is never used",
+                    c.name, c.location(), self.constants[next].location(),
+                ));
+            }
+        }
+    }
+
+    // Finds the definition that a reference to name in the definition at idx refers to.
+    // This is the latest definition before idx, or the last definition if there is none.
+    fn reference(&self, idx: usize, name: &str) -> Option<usize> {
+        let defs = self.index.get(name)?;
+        defs.iter().rev().find(|&&d| d < idx).or(defs.last()).copied()
+    }
+
+    // Gets the last definition of a constant, which is the one seen by scripts.
+    pub fn get(&self, name: &str) -> Option<&Constant> {
+        self.index.get(name).and_then(|defs| defs.last()).map(|&idx| &self.constants[idx])
+    }
+
+    // Kept constants have no substitution, since they remain as variables.
+    pub fn get_substitution(&self, name: &str) -> Option<&str> {
+        match self.get(name) {
+            Some(c) if !c.keep => Some(c.substitution()),
+            _ => None,
+        }
+    }
+
+    pub fn is_flattened(&self, name: &str) -> bool {
+        self.get_substitution(name).is_some()
+    }
+
+    // Iterates over the last definition of each constant, with dependencies first.
+    // Only meaningful once resolve() has been called.
+    pub fn iter_resolved(&self) -> impl Iterator<Item = &Constant> {
+        self.order.iter()
+            .filter(|&&idx| self.is_last_definition(idx))
+            .map(|&idx| &self.constants[idx])
+    }
+
+    fn is_last_definition(&self, idx: usize) -> bool {
+        self.index[&self.constants[idx].name].last() == Some(&idx)
+    }
+
+    // Fails if any constant calls one of the given functions.
+    pub fn check_runtime_calls(&self, runtime_functions: &[String]) -> Result<(), String> {
+        let mut errors = Vec::new();
+        for c in self.constants.iter().filter(|c| !c.keep) {
+            for (idx, t) in c.tokens.iter().enumerate() {
+                if let (Identifier(s), Some(OpenBracket(b))) = (t, c.tokens.get(idx+1)) {
+                    if b == "(" && runtime_functions.iter().any(|f| f == s) {
+                        errors.push(format!(
+                            "Constant {} (at {}) calls {}(), which would run again at every usage site; assign it in init.gml or mark it with //#RCFKEEP instead",
+                            c.name, c.location(), s,
+                        ));
+                    }
+                }
+            }
+        }
+        if errors.is_empty() { Ok(()) }
+        else { Err(errors.join("\n")) }
+    }
+
+    // Problems that don't prevent an export, such as duplicate definitions.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+}
+
+// Concatenates value tokens, placing spaces only between adjacent words.
+// Identifiers for which lookup returns a value are replaced with it.
+fn join_tokens<'a>(tokens: &[Token], mut lookup: impl FnMut(&str) -> Option<&'a str>) -> String {
+    let mut out = String::new();
+    let mut needs_space = false;
+    for t in tokens.iter() {
+        match t {
+            Identifier(s) => {
+                if needs_space { out.push(' ') };
+                match lookup(s) {
+                    Some(v) => out.push_str(v),
+                    None => out.push_str(s),
+                }
+                needs_space = true;
+            }
+            Literal(s) => {
+                if needs_space { out.push(' ') };
+                out.push_str(s);
+                needs_space = true;
+            }
+            Symbol(s) | OpenBracket(s) | CloseBracket(s) => {
+                out.push_str(s);
+                needs_space = false;
+            }
+            Dot => {
+                out.push('.');
+                needs_space = false;
+            }
+            _ => (),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flt::read_constant_script;
+
+    fn resolve(src: &str) -> Result<ConstantTable, String> {
+        let mut table = ConstantTable::new();
+        read_constant_script(&mut table, src, "user_event3.gml", &[])?;
+        table.resolve()?;
+        Ok(table)
+    }
+
+    fn value<'a>(table: &'a ConstantTable, name: &str) -> &'a str {
+        table.get_substitution(name).unwrap()
+    }
+
+    #[test]
+    fn resolves_forward_references() {
+        let table = resolve("RUN = WALK * 2;\nWALK = 3;").unwrap();
+        assert_eq!(value(&table, "RUN"), "6");
+    }
+
+    #[test]
+    fn references_see_the_definition_in_effect() {
+        let table = resolve("A = 1;\nB = A;\nA = A + 1;\nC = 1;\nD = C;\nC = 5;").unwrap();
+        assert_eq!(value(&table, "A"), "2");
+        assert_eq!(value(&table, "B"), "1");
+        assert_eq!(value(&table, "C"), "5");
+        assert_eq!(value(&table, "D"), "1");
+        assert_eq!(table.len(), 4);
+    }
+
+    #[test]
+    fn warns_about_unused_definitions() {
+        let table = resolve("A = 1;\nA = 2;").unwrap();
+        assert_eq!(table.warnings(), ["Constant A is defined at both user_event3.gml:1 and user_event3.gml:2; the first definition is never used"]);
+        assert!(resolve("A = 1;\nB = A;\nA = 2;").unwrap().warnings().is_empty());
+        assert!(resolve("SPEED = 3;\nSPEED = SPEED + 1;").unwrap().warnings().is_empty());
+    }
+
+    #[test]
+    fn orders_by_dependency() {
+        let table = resolve("//#RCFKEEP\nA = B + 1;\n//#RCFKEEP\nB = 2;").unwrap();
+        let names: Vec<&str> = table.iter_resolved().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["B", "A"]);
+        assert_eq!(table.get("A").unwrap().value, "B+1");
+    }
+
+    #[test]
+    fn reports_cycles() {
+        let err = resolve("A = B + 1;\nB = A;").err().unwrap();
+        assert!(err.contains("depends on itself"), "{}", err);
+        assert!(resolve("SPEED = SPEED + 1;").is_err());
+    }
+
+    #[test]
+    fn parenthesizes_compound_values() {
+        let table = resolve("A = B + 1;\nB = x;").unwrap();
+        assert_eq!(value(&table, "A"), "(x+1)");
+        assert_eq!(value(&table, "B"), "x");
+    }
+}
@@ -0,0 +1,346 @@
+use crate::export_project::flattener_scripts as flt;
+use flt::token::Token;
+use flt::token::Token::*;
+use flt::scanner::RcfScanner;
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+pub enum Value {
+    Real(f64),
+    Bool(bool),
+    Str(String, char), // contents (escapes left as written) and quote character
+}
+
+use flt::expression::Value::*;
+
+impl Value {
+
+    pub fn to_gml(&self) -> String {
+        match self {
+            Real(n) => {
+                if n.fract() == 0.0 && n.abs() < 1e15 { format!("{}", *n as i64) }
+                else { format!("{}", n) }
+            },
+            Bool(b) => b.to_string(),
+            Str(s, q) => format!("{q}{s}{q}"),
+        }
+    }
+
+    // GML treats any real above 0.5 as true, and 0.5 or below as false.
+    pub fn is_truthy(&self) -> Option<bool> {
+        match self {
+            Real(n) => Some(*n > 0.5),
+            Bool(b) => Some(*b),
+            Str(_, _) => None,
+        }
+    }
+
+    fn as_real(&self) -> Option<f64> {
+        match self {
+            Real(n) => Some(*n),
+            Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+            Str(_, _) => None,
+        }
+    }
+
+}
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+enum Item {
+    Operand(Value),
+    Operator(&'static str),
+    Open,
+    Close,
+}
+
+// Folds a GML expression down to a single literal, if every operand is a known literal.
+// Lone literals are returned as None, so that their original formatting is kept.
+pub fn fold_expression(src: &str) -> Option<String> {
+    let tokens = significant_tokens(src)?;
+    if tokens.len() < 2 { return None };
+    evaluate(&tokens).map(|v| v.to_gml())
+}
+
+// Whether an expression can be substituted next to any operator without changing its meaning.
+// Covers lone literals, identifier chains with calls or indexing, and bracketed expressions.
+pub fn is_atomic(src: &str) -> bool {
+    let tokens = match significant_tokens(src) {
+        Some(t) => t,
+        None => return false,
+    };
+
+    let mut idx = 0;
+    match tokens.first() {
+        Some(Literal(_)) => return tokens.len() == 1,
+        Some(OpenBracket(_)) => {
+            return matching_bracket(&tokens, 0) == Some(tokens.len() - 1);
+        }
+        Some(t @ Identifier(_)) if !t.is_word_operator() => {
+            idx += 1;
+            while let (Some(Dot), Some(Identifier(_))) = (tokens.get(idx), tokens.get(idx+1)) {
+                idx += 2;
+            }
+            while let Some(OpenBracket(_)) = tokens.get(idx) {
+                match matching_bracket(&tokens, idx) {
+                    Some(close) => idx = close + 1,
+                    None => return false,
+                }
+            }
+        }
+        _ => return false,
+    }
+    idx == tokens.len()
+}
+
+pub fn matching_bracket(tokens: &[Token], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (idx, t) in tokens.iter().enumerate().skip(open) {
+        match t {
+            OpenBracket(_) => depth += 1,
+            CloseBracket(_) => {
+                depth -= 1;
+                if depth == 0 { return Some(idx) };
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+fn significant_tokens(src: &str) -> Option<Vec<Token>> {
+    let mut s = RcfScanner::new(src);
+    let tokens = s.scan_tokens().ok()?;
+    Some(tokens.into_iter()
+        .filter(|t| !t.is_formatting())
+        .collect())
+}
+
+// Evaluates a sequence of significant tokens (no whitespace or comments).
+// Returns None if the expression contains anything other than literals and supported operators.
+pub fn evaluate(tokens: &[Token]) -> Option<Value> {
+    let items = to_items(tokens)?;
+    let mut pos = 0;
+    let value = parse_binary(&items, &mut pos, 0)?;
+    if pos == items.len() { Some(value) }
+    else { None }
+}
+
+fn to_items(tokens: &[Token]) -> Option<Vec<Item>> {
+    let mut items = Vec::new();
+    let mut iter = tokens.iter().peekable();
+    while let Some(t) = iter.next() {
+        let item = match t {
+            Literal(s) => Item::Operand(parse_literal(s)?),
+            OpenBracket(s) if s == "(" => Item::Open,
+            CloseBracket(s) if s == ")" => Item::Close,
+            Identifier(s) => Item::Operator(match s.as_str() {
+                "and" => "&&",
+                "or" => "||",
+                "xor" => "^^",
+                "not" => "!",
+                "div" => "div",
+                "mod" => "%",
+                _ => return None,
+            }),
+            Symbol(s) => {
+                let next = iter.peek();
+                let paired = |c: &str| matches!(next, Some(Symbol(n)) if n == c);
+                let op = match s.as_str() {
+                    "+" => "+",
+                    "-" => "-",
+                    "*" => "*",
+                    "/" => "/",
+                    "%" => "%",
+                    "==" => "==",
+                    "!" if next == Some(&&Equal) => "!=",
+                    "!" => "!",
+                    "<" if next == Some(&&Equal) => "<=",
+                    "<" if paired(">") => "!=",
+                    "<" if paired("<") => return None,
+                    "<" => "<",
+                    ">" if next == Some(&&Equal) => ">=",
+                    ">" if paired(">") => return None,
+                    ">" => ">",
+                    "&" if paired("&") => "&&",
+                    "|" if paired("|") => "||",
+                    "^" if paired("^") => "^^",
+                    _ => return None,
+                };
+                if matches!(op, "!=" | "<=" | ">=" | "&&" | "||" | "^^") {
+                    iter.next();
+                }
+                Item::Operator(op)
+            }
+            _ => return None,
+        };
+        items.push(item);
+    }
+    Some(items)
+}
+
+fn parse_literal(s: &str) -> Option<Value> {
+    match s {
+        "true" => Some(Bool(true)),
+        "false" => Some(Bool(false)),
+        _ => {
+            let first = s.chars().next()?;
+            if first == '"' || first == '\'' || first == '`' {
+                Some(Str(String::from(&s[1..s.len()-1]), first))
+            } else if first.is_ascii_digit() {
+                s.parse::<f64>().ok().map(Real)
+            } else {
+                None // noone, pi, etc. are left alone
+            }
+        }
+    }
+}
+
+fn precedence(op: &str) -> Option<u8> {
+    match op {
+        "||" => Some(1),
+        "^^" => Some(2),
+        "&&" => Some(3),
+        "==" | "!=" | "<" | "<=" | ">" | ">=" => Some(4),
+        "+" | "-" => Some(5),
+        "*" | "/" | "div" | "%" => Some(6),
+        _ => None,
+    }
+}
+
+fn parse_binary(items: &[Item], pos: &mut usize, min_prec: u8) -> Option<Value> {
+    let mut left = parse_unary(items, pos)?;
+    while let Some(Item::Operator(op)) = items.get(*pos) {
+        let prec = match precedence(op) {
+            Some(p) if p > min_prec => p,
+            _ => break,
+        };
+        *pos += 1;
+        let right = parse_binary(items, pos, prec)?;
+        left = apply_binary(op, left, right)?;
+    }
+    Some(left)
+}
+
+fn parse_unary(items: &[Item], pos: &mut usize) -> Option<Value> {
+    let item = items.get(*pos)?;
+    *pos += 1;
+    match item {
+        Item::Operand(v) => Some(v.clone()),
+        Item::Open => {
+            let v = parse_binary(items, pos, 0)?;
+            if items.get(*pos) != Some(&Item::Close) { return None };
+            *pos += 1;
+            Some(v)
+        }
+        Item::Operator("-") => Some(Real(-parse_unary(items, pos)?.as_real()?)),
+        Item::Operator("+") => Some(Real(parse_unary(items, pos)?.as_real()?)),
+        Item::Operator("!") => Some(Bool(!parse_unary(items, pos)?.is_truthy()?)),
+        _ => None,
+    }
+}
+
+fn apply_binary(op: &str, left: Value, right: Value) -> Option<Value> {
+    // Strings only support concatenation and equality
+    if let (Str(a, qa), Str(b, qb)) = (&left, &right) {
+        return match op {
+            "+" if qa == qb => Some(Str(format!("{a}{b}"), *qa)),
+            "==" => Some(Bool(a == b)),
+            "!=" => Some(Bool(a != b)),
+            _ => None,
+        };
+    }
+
+    match op {
+        "&&" => return Some(Bool(left.is_truthy()? && right.is_truthy()?)),
+        "||" => return Some(Bool(left.is_truthy()? || right.is_truthy()?)),
+        "^^" => return Some(Bool(left.is_truthy()? != right.is_truthy()?)),
+        _ => (),
+    }
+
+    let (a, b) = (left.as_real()?, right.as_real()?);
+    let out = match op {
+        "+" => Real(a + b),
+        "-" => Real(a - b),
+        "*" => Real(a * b),
+        "/" if b != 0.0 => Real(a / b),
+        "div" if b != 0.0 => Real((a / b).trunc()),
+        "%" if b != 0.0 => Real(a % b),
+        "==" => Bool(a == b),
+        "!=" => Bool(a != b),
+        "<" => Bool(a < b),
+        "<=" => Bool(a <= b),
+        ">" => Bool(a > b),
+        ">=" => Bool(a >= b),
+        _ => return None,
+    };
+    match out {
+        Real(n) if !n.is_finite() => None,
+        v => Some(v),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn folds_arithmetic() {
+        assert_eq!(fold_expression("1 + 2").as_deref(), Some("3"));
+        assert_eq!(fold_expression("7 div 2").as_deref(), Some("3"));
+        assert_eq!(fold_expression("7 mod 4").as_deref(), Some("3"));
+        assert_eq!(fold_expression("1 / 4").as_deref(), Some("0.25"));
+        assert_eq!(fold_expression("-(2 + 3)").as_deref(), Some("-5"));
+    }
+
+    #[test]
+    fn respects_precedence() {
+        assert_eq!(fold_expression("2 + 3 * 4").as_deref(), Some("14"));
+        assert_eq!(fold_expression("(2 + 3) * 4").as_deref(), Some("20"));
+        assert_eq!(fold_expression("10 - 4 - 3").as_deref(), Some("3"));
+        assert_eq!(fold_expression("1 + 1 == 2 && 3 > 2").as_deref(), Some("true"));
+        assert_eq!(fold_expression("1 < 2 || 1 / 0 > 1"), None);
+    }
+
+    #[test]
+    fn folds_logic_and_comparisons() {
+        assert_eq!(fold_expression("!true").as_deref(), Some("false"));
+        assert_eq!(fold_expression("true and not false").as_deref(), Some("true"));
+        assert_eq!(fold_expression("true xor true").as_deref(), Some("false"));
+        assert_eq!(fold_expression("3 != 3").as_deref(), Some("false"));
+        assert_eq!(fold_expression("3 <= 3").as_deref(), Some("true"));
+        assert_eq!(fold_expression("0.4 || 0").as_deref(), Some("false"));
+        assert_eq!(fold_expression("0.5 || 0").as_deref(), Some("false"));
+        assert_eq!(fold_expression("0.6 && 1").as_deref(), Some("true"));
+    }
+
+    #[test]
+    fn folds_strings() {
+        assert_eq!(fold_expression("\"a\" + \"b\"").as_deref(), Some("\"ab\""));
+        assert_eq!(fold_expression("\"a\" == \"a\"").as_deref(), Some("true"));
+        assert_eq!(fold_expression("\"a\" * 2"), None);
+    }
+
+    #[test]
+    fn leaves_unknowns_and_lone_literals() {
+        assert_eq!(fold_expression("4"), None);
+        assert_eq!(fold_expression("SPEED + 1"), None);
+        assert_eq!(fold_expression("max(1, 2)"), None);
+        assert_eq!(fold_expression("1 / 0"), None);
+        assert_eq!(fold_expression("1 << 2"), None);
+    }
+
+    #[test]
+    fn detects_atomic_expressions() {
+        assert!(is_atomic("4"));
+        assert!(is_atomic("player_id.SPEED"));
+        assert!(is_atomic("arr[0][1]"));
+        assert!(is_atomic("max(1, 2)"));
+        assert!(is_atomic("(1 + 2)"));
+        assert!(!is_atomic("1 + 2"));
+        assert!(!is_atomic("(1) + (2)"));
+        assert!(!is_atomic("-4"));
+        assert!(!is_atomic("not x"));
+    }
+}
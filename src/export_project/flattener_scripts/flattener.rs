@@ -2,13 +2,18 @@ use crate::export_project::flattener_scripts as flt;
 use flt::token::Token;
 use flt::token::Token::*;
 use flt::compressor::compress_whitespace;
+use flt::constant_table::ConstantTable;
+use flt::locals::find_shadowing_locals;
+use flt::write_check::{get_write_kind, is_statement_start, is_substatement, next_significant, previous_significant};
+use flt::expression::{evaluate, matching_bracket, Value};
 use std::collections::HashMap;
 
 pub struct Flattener {
-    user_event : String,
+    user_event : Option<u8>,
     skip_whitespace : bool,
     skip_comments : bool,
     deformat_active : bool,
+    noflatten_active : bool,
     needs_space : bool,
     stack : String,
     output : String,
@@ -17,32 +22,50 @@ pub struct Flattener {
 impl Flattener {
 
     pub fn new(user_event: Option<u8>, skip_whitespace: bool, skip_comments: bool) -> Flattener {
-        let user_event = {
-            if let Some(ue) = user_event { ue.to_string() }
-            else { String::from("/*/*/*/") } // cannot be read as identifier
-        };
         let deformat_active = false;
+        let noflatten_active = false;
         let needs_space = false;
         let stack = String::new();
         let output = String::new();
-        Flattener{ user_event, skip_whitespace, skip_comments, deformat_active, needs_space, stack, output }
+        Flattener{ user_event, skip_whitespace, skip_comments, deformat_active, noflatten_active, needs_space, stack, output }
     }
 
-    pub fn flatten_program(&mut self, ts: &Vec<Token>, map: &HashMap<String, String>) -> Result<String, String> {
+    pub fn flatten_program(&mut self, ts: &[Token], table: &ConstantTable, file_name: &str) -> Result<String, String> {
         self.output = String::new();
         let mut is_empty = true;
+        let mut line = 1;
 
         // For ignore tags
         let mut ignoring = false;
 
+        // For flatten opt-outs, which can apply to the whole file
+        let noflatten_file = ts.contains(&NoFlatten);
+        self.noflatten_active = noflatten_file;
+
+        // For locals sharing a name with a constant, which are left untouched
+        let shadowing = find_shadowing_locals(ts, table);
+
         // For safe whitespace stripping
         let mut escape_handled = false;
 
-        // For stripping RCF user event
-        let mut closing_user_event = false;
-        let mut eating_semicolon = false;
+        // For stripping RCF user event calls, which are skipped over entirely
+        let removed_calls = match self.user_event {
+            Some(ue) => find_user_event_calls(ts, ue, file_name)?,
+            None => HashMap::new(),
+        };
+        let mut skip_until = 0;
 
-        for t in ts {
+        for (idx, t) in ts.iter().enumerate() {
+            line += t.line_breaks();
+            if idx < skip_until { continue };
+            if let Some(&(end, substatement)) = removed_calls.get(&idx) {
+                // Matching user_event call found -> discard it, along with any receiver
+                self.flush_stack();
+                // An if or loop body can't just vanish, or the next statement would take its place
+                if substatement { self.output.push_str("{}") };
+                skip_until = end;
+                continue;
+            }
             if ignoring { match t {
                 IgnoreEnd => ignoring = false,
                 _ => (),
@@ -51,8 +74,6 @@ impl Flattener {
                 IgnoreBegin => {
                     self.flush_stack();
                     ignoring = true;
-                    closing_user_event = false;
-                    eating_semicolon = false;
                 }
                 IgnoreEnd => {
                     return Err(String::from("Reached unpaired end-ignore declaration"));
@@ -61,14 +82,29 @@ impl Flattener {
                 DeformatBegin => {
                     self.flush_stack();
                     self.deformat_active = true;
-                    closing_user_event = false;
-                    eating_semicolon = false;
                 }
                 DeformatEnd => {
                     self.flush_stack();
                     self.deformat_active = false;
-                    closing_user_event = false;
-                    eating_semicolon = false;
+                }
+
+                IfBegin(_) | Else | IfEnd => {
+                    return Err(String::from("Conditional declarations must be resolved before flattening"));
+                }
+
+                NoFlatten => {
+                    self.flush_stack();
+                }
+                NoFlattenBegin => {
+                    self.flush_stack();
+                    self.noflatten_active = true;
+                }
+                NoFlattenEnd => {
+                    if !self.noflatten_active {
+                        return Err(String::from("Reached unpaired end-noflatten declaration"));
+                    }
+                    self.flush_stack();
+                    self.noflatten_active = noflatten_file;
                 }
 
                 NewLine => {
@@ -81,18 +117,14 @@ impl Flattener {
                         escape_handled = true;
                         self.needs_space = false;
                     }
-                    closing_user_event = false;
-                    eating_semicolon = false;
                 }
                 Semicolon => {
                     self.flush_stack();
                     self.needs_space = false;
-                    if !eating_semicolon && (!self.skips_whitespace() || !escape_handled) {
+                    if !self.skips_whitespace() || !escape_handled {
                         self.output.push(';');
                     }
                     escape_handled = true;
-                    closing_user_event = false;
-                    eating_semicolon = false;
                 }
                 Whitespace(s) => {
                     self.flush_stack();
@@ -102,7 +134,6 @@ impl Flattener {
                         self.output.push(' ');
                         self.needs_space = false;
                     }
-                    closing_user_event = false;
                 },
 
                 LongComment(s) => {
@@ -113,7 +144,16 @@ impl Flattener {
                         escape_handled = false;
                         is_empty = false;
                     }
-                    closing_user_event = false;
+                },
+                Keep => {
+                    // Only meaningful in the RCF user_event, so it's treated as a regular comment
+                    self.flush_stack();
+                    self.needs_space = self.skips_comments();
+                    if !self.skips_comments() {
+                        self.output.push_str("//#RCFKEEP");
+                        escape_handled = false;
+                        is_empty = false;
+                    }
                 },
                 ShortComment(s) => {
                     self.flush_stack();
@@ -123,24 +163,25 @@ impl Flattener {
                         escape_handled = false;
                         is_empty = false;
                     }
-                    closing_user_event = false;
                 },
 
                 Identifier(s) => {
-                    if let Some(val) = map.get(s) {
+                    if let (Some(val), false, false) = (table.get_substitution(s), self.noflatten_active, shadowing.shadowed.contains(&idx)) {
+                        // Substituting a write target would produce invalid GML (e.g. 1 = 2;)
+                        if let Some(kind) = get_write_kind(ts, idx) {
+                            return Err(format!("{}:{}: Constant {} is {}, so it can't be flattened", file_name, line, s, kind));
+                        }
                         // Match should correspond to a constant,
                         // so insert that and toss out the contents of self.stack.
                         if self.needs_space && self.skips_whitespace() { self.output.push(' ') };
-                        self.output.push_str(&val);
+                        self.output.push_str(val);
                         self.stack = String::new();
                         self.needs_space = true;
                     }
-                    else { // This implicitly catches user_event calls, too
+                    else {
                         self.stack.push_str(&s);
                     }
                     escape_handled = false;
-                    closing_user_event = false;
-                    eating_semicolon = false;
                     is_empty = false;
                 },
 
@@ -152,25 +193,16 @@ impl Flattener {
                         self.needs_space = false;
                     }
                     escape_handled = false;
-                    closing_user_event = false;
-                    eating_semicolon = false;
                     is_empty = false;
                 }
                 
                 Literal(s) => {
-                    if self.stack == "user_event(" && *s == self.user_event {
-                        self.stack.push_str(&s);
-                        closing_user_event = true;
-                    } else {
-                        self.flush_stack();
-                        if self.needs_space && self.skips_whitespace() { self.output.push(' ') };
-                        self.output.push_str(&s);
-                        closing_user_event = false;
-                    }
-                    
+                    self.flush_stack();
+                    if self.needs_space && self.skips_whitespace() { self.output.push(' ') };
+                    self.output.push_str(&s);
+
                     self.needs_space = true;
                     escape_handled = false;
-                    eating_semicolon = false;
                     is_empty = false;
                 },
 
@@ -180,39 +212,23 @@ impl Flattener {
 
                     escape_handled = s == ","; 
                     self.needs_space = false;
-                    closing_user_event = false;
-                    eating_semicolon = false;
                     is_empty = false;
                 },
 
                 OpenBracket(s) => {
-                    if self.stack == "user_event" && s == "(" {
-                        self.stack.push_str(&s);
-                    } else {
-                        self.flush_stack();
-                        self.output.push_str(&s);
-                    }
+                    self.flush_stack();
+                    self.output.push_str(&s);
 
                     escape_handled = true;
                     self.needs_space = false;
-                    closing_user_event = false;
-                    eating_semicolon = false;
                 },
 
                 CloseBracket(s) => {
-                    if closing_user_event && s == ")" {
-                        // matching user_event found -> discard
-                        self.stack = String::new();
-                        eating_semicolon = true;
-                    } else {
-                        self.flush_stack();
-                        self.output.push_str(&s);
-                        eating_semicolon = false;
-                    }
+                    self.flush_stack();
+                    self.output.push_str(&s);
 
                     escape_handled = s == "}"; // need to test if ) and ] behave nicely
                     self.needs_space = false;
-                    closing_user_event = false;
                 },
 
                 Equal => {
@@ -220,8 +236,6 @@ impl Flattener {
                     self.output.push('=');
                     escape_handled = false;
                     self.needs_space = false;
-                    closing_user_event = false;
-                    eating_semicolon = false;
                     is_empty = false;
                 },
 
@@ -250,4 +264,76 @@ impl Flattener {
             self.needs_space = true;
         }
     }
-}
\ No newline at end of file
+}
+// Finds every call to the given user_event outside of ignored regions, keyed by the index the call starts at.
+// Each maps to the index just past the call, and whether the call is the body of an if, else or loop.
+fn find_user_event_calls(ts: &[Token], user_event: u8, file_name: &str) -> Result<HashMap<usize, (usize, bool)>, String> {
+    let mut calls = HashMap::new();
+    let mut line = 1;
+    let mut ignoring = false;
+
+    for (idx, t) in ts.iter().enumerate() {
+        line += t.line_breaks();
+        match t {
+            IgnoreBegin => ignoring = true,
+            IgnoreEnd => ignoring = false,
+            Identifier(s) if s == "user_event" && !ignoring => match find_user_event_call(ts, idx, user_event) {
+                Some(Ok((start, end))) => { calls.insert(start, (end, is_substatement(ts, start))); }
+                Some(Err(reason)) => {
+                    return Err(format!("{}:{}: Call to user_event({}) can't be removed from the export, since {}", file_name, line, user_event, reason));
+                }
+                None => (),
+            },
+            _ => (),
+        }
+    }
+
+    Ok(calls)
+}
+
+// Checks whether the identifier at idx is a call to the given user_event.
+// Returns where the call starts (including any self receiver) and the index just past it (and its semicolon)
+// if the whole statement can be removed, or the reason it can't be if the call is used in some other way.
+fn find_user_event_call(ts: &[Token], idx: usize, user_event: u8) -> Option<Result<(usize, usize), String>> {
+    let open = next_significant(ts, idx + 1)?;
+    if ts[open] != OpenBracket(String::from("(")) { return None };
+    let close = matching_bracket(ts, open)?;
+
+    let arg: Vec<Token> = ts[open+1..close].iter()
+        .filter(|t| !t.is_formatting())
+        .cloned()
+        .collect();
+    match evaluate(&arg) {
+        Some(Value::Real(n)) if n == user_event as f64 => (),
+        _ => return None,
+    }
+
+    // Walk back over the receiver, which may be spaced out (e.g. self . user_event(3))
+    let mut start = idx;
+    let mut parts = Vec::new();
+    while let Some(dot) = previous_significant(ts, start).filter(|&p| ts[p] == Dot) {
+        match previous_significant(ts, dot).map(|p| (p, &ts[p])) {
+            Some((p, Identifier(s))) => {
+                parts.push(s.as_str());
+                start = p;
+            }
+            _ => return Some(Err(String::from("it's called through an expression"))),
+        }
+    }
+    parts.reverse();
+    if !parts.is_empty() && parts != ["self"] {
+        return Some(Err(format!("it's called through {}", parts.join("."))));
+    }
+    if !is_statement_start(ts, start) {
+        return Some(Err(String::from("its result is used")));
+    }
+
+    let mut end = close + 1;
+    match next_significant(ts, end).map(|n| (n, &ts[n])) {
+        None | Some((_, NewLine)) => (),
+        Some((n, Semicolon)) => end = n + 1,
+        Some((_, CloseBracket(s))) if s == "}" => (),
+        Some(_) => return Some(Err(String::from("its result is used"))),
+    }
+    Some(Ok((start, end)))
+}
@@ -0,0 +1,113 @@
+use crate::export_project::flattener_scripts as flt;
+use flt::token::Token;
+use flt::token::Token::*;
+use flt::constant_table::ConstantTable;
+use flt::write_check::{next_significant, previous_significant};
+use std::collections::HashSet;
+
+pub struct LocalDeclaration {
+    pub name: String,
+    pub kind: &'static str,
+    pub line: usize,
+}
+
+pub struct Shadowing {
+    pub shadowed: HashSet<usize>, // indices of identifiers that refer to a local, not a constant
+    pub declarations: Vec<LocalDeclaration>,
+}
+
+// Finds local variables and script parameters that share a name with a flattened constant.
+// Locals last from their declaration to the end of the script or #define they're declared in.
+pub fn find_shadowing_locals(ts: &[Token], table: &ConstantTable) -> Shadowing {
+    let mut shadowed = HashSet::new();
+    let mut declarations = Vec::new();
+    let mut locals: HashSet<String> = HashSet::new();
+    let mut line = 1;
+    let mut ignoring = false;
+
+    for (idx, t) in ts.iter().enumerate() {
+        line += t.line_breaks();
+        match t {
+            IgnoreBegin => ignoring = true,
+            IgnoreEnd => ignoring = false,
+            Identifier(_) if ignoring || is_accessed(ts, idx) => (),
+            Identifier(s) if s == "define" && follows_hash(ts, idx) => {
+                locals.clear();
+                for name in read_parameters(ts, idx) {
+                    if table.is_flattened(&name) && locals.insert(name.clone()) {
+                        declarations.push(LocalDeclaration{ name, kind: "Parameter", line });
+                    }
+                }
+            }
+            Identifier(s) if s == "var" => {
+                for name in read_var_names(ts, idx) {
+                    if table.is_flattened(&name) && locals.insert(name.clone()) {
+                        declarations.push(LocalDeclaration{ name, kind: "Local variable", line });
+                    }
+                }
+            }
+            Identifier(s) if locals.contains(s) => { shadowed.insert(idx); }
+            _ => (),
+        }
+    }
+
+    Shadowing{ shadowed, declarations }
+}
+
+// Reads the names declared by the var statement at idx.
+fn read_var_names(ts: &[Token], idx: usize) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut expecting_name = true;
+    let mut depth = 0;
+
+    for t in ts.iter().skip(idx + 1) {
+        match t {
+            Identifier(s) if expecting_name && depth == 0 => {
+                names.push(s.clone());
+                expecting_name = false;
+            }
+            Symbol(s) if s == "," && depth == 0 => expecting_name = true,
+            OpenBracket(_) => depth += 1,
+            CloseBracket(_) if depth == 0 => break,
+            CloseBracket(_) => depth -= 1,
+            Semicolon | NewLine if depth == 0 => break,
+            _ => (),
+        }
+    }
+
+    names
+}
+
+// Reads the parameter list of the #define at idx, if it has one.
+fn read_parameters(ts: &[Token], idx: usize) -> Vec<String> {
+    let mut names = Vec::new();
+    let open = next_significant(ts, idx + 1)
+        .filter(|&n| matches!(ts[n], Identifier(_)))
+        .and_then(|n| next_significant(ts, n + 1))
+        .filter(|&n| ts[n] == OpenBracket(String::from("(")));
+    let Some(open) = open else { return names };
+
+    let mut expecting_name = true;
+    for t in ts.iter().skip(open + 1) {
+        match t {
+            Identifier(s) if expecting_name => {
+                names.push(s.clone());
+                expecting_name = false;
+            }
+            Symbol(s) if s == "," => expecting_name = true,
+            CloseBracket(_) | NewLine => break,
+            _ => (),
+        }
+    }
+
+    names
+}
+
+fn follows_hash(ts: &[Token], idx: usize) -> bool {
+    idx > 0 && ts[idx-1] == Symbol(String::from("#"))
+}
+
+// Identifiers accessed through another instance (e.g. other.SPEED) never refer to locals.
+fn is_accessed(ts: &[Token], idx: usize) -> bool {
+    previous_significant(ts, idx).is_some_and(|p| ts[p] == Dot)
+}
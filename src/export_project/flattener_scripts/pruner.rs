@@ -0,0 +1,152 @@
+use crate::export_project::flattener_scripts as flt;
+use flt::token::Token;
+use flt::token::Token::*;
+use flt::expression::{evaluate, matching_bracket};
+use flt::write_check::{is_substatement, next_code};
+use std::ops::Range;
+
+struct IfStatement {
+    condition: Option<bool>, // None if the condition isn't constant
+    then_branch: Range<usize>,
+    else_branch: Option<Range<usize>>,
+    end: usize,
+}
+
+// Removes if statements whose conditions are constant, keeping only the live branch.
+// Statements that can't be parsed with confidence are left untouched.
+pub fn prune_dead_branches(ts: &[Token]) -> String {
+    let mut out = String::new();
+    let mut idx = 0;
+
+    while idx < ts.len() {
+        if ts[idx] == Identifier(String::from("if")) {
+            if let Some(IfStatement{ condition: Some(cond), then_branch, else_branch, end }) = parse_if(ts, idx) {
+                let live = if cond { Some(then_branch) } else { else_branch };
+                let substatement = is_substatement(ts, idx);
+                match live {
+                    // Blocks are unwrapped where it's safe to do so (GML blocks don't have their own scope)
+                    Some(r) if !substatement && ts[r.start] == OpenBracket(String::from("{")) => {
+                        out.push_str(&prune_dead_branches(&ts[r.start+1..r.end-1]));
+                    }
+                    Some(r) => {
+                        out.push_str(&prune_dead_branches(&ts[r.clone()]));
+                        // A branch that ended at an else has no semicolon of its own
+                        if !matches!(&ts[r.end-1], Semicolon | CloseBracket(_)) { out.push(';') };
+                    }
+                    None => if substatement { out.push_str("{}") }, // keeps the enclosing statement intact
+                }
+                idx = end;
+                continue;
+            }
+        }
+        out.push_str(&ts[idx].to_source());
+        idx += 1;
+    }
+
+    out
+}
+
+fn parse_if(ts: &[Token], start: usize) -> Option<IfStatement> {
+    let open = next_code(ts, start + 1)?;
+    if ts[open] != OpenBracket(String::from("(")) { return None };
+    let close = matching_bracket(ts, open)?;
+
+    let mut body = next_code(ts, close + 1)?;
+    if ts[body] == Identifier(String::from("then")) {
+        body = next_code(ts, body + 1)?;
+    }
+    // The bracketed group must make up the entire condition
+    if matches!(ts[body], Symbol(_) | Dot | Equal | Semicolon) { return None };
+
+    let then_end = statement_end(ts, body)?;
+    let mut end = then_end;
+    let mut else_branch = None;
+    if let Some(e) = next_code(ts, then_end) {
+        if ts[e] == Identifier(String::from("else")) {
+            let else_start = next_code(ts, e + 1)?;
+            end = statement_end(ts, else_start)?;
+            else_branch = Some(else_start..end);
+        }
+    }
+
+    let condition: Vec<Token> = ts[open+1..close].iter()
+        .filter(|t| !t.is_formatting())
+        .cloned()
+        .collect();
+    let condition = evaluate(&condition).and_then(|v| v.is_truthy());
+
+    Some(IfStatement{ condition, then_branch: body..then_end, else_branch, end })
+}
+
+// Finds the end (exclusive) of the statement starting at idx.
+// Single statements must be terminated by a semicolon on the same line, or by an else (e.g. if (a) b = 1 else c = 2;).
+fn statement_end(ts: &[Token], idx: usize) -> Option<usize> {
+    match &ts[idx] {
+        OpenBracket(s) if s == "{" => Some(matching_bracket(ts, idx)? + 1),
+        Identifier(s) if s == "if" => Some(parse_if(ts, idx)?.end),
+        _ => {
+            let mut depth = 0;
+            let mut last = idx;
+            for (i, t) in ts.iter().enumerate().skip(idx) {
+                match t {
+                    Identifier(s) if s == "else" && depth == 0 => return if last > idx { Some(last) } else { None },
+                    OpenBracket(_) => depth += 1,
+                    CloseBracket(_) if depth == 0 => return None,
+                    CloseBracket(_) => depth -= 1,
+                    Semicolon if depth == 0 => return Some(i + 1),
+                    NewLine if depth == 0 => return None,
+                    _ => (),
+                }
+                if !t.is_formatting() { last = i + 1 };
+            }
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flt::scanner::RcfScanner;
+
+    fn prune(src: &str) -> String {
+        let mut s = RcfScanner::new(src);
+        prune_dead_branches(&s.scan_tokens().unwrap())
+    }
+
+    #[test]
+    fn keeps_the_live_branch() {
+        assert_eq!(prune("if (true) a = 1; else b = 2;"), "a = 1;");
+        assert_eq!(prune("if (false) a = 1; else b = 2;"), "b = 2;");
+        assert_eq!(prune("if (1 > 2) a = 1;\nc = 3;"), "\nc = 3;");
+        assert_eq!(prune("if (true) { a = 1; }"), " a = 1; ");
+    }
+
+    #[test]
+    fn ends_single_statements_at_else() {
+        assert_eq!(prune("if (false) a = 1 else b = 2;"), "b = 2;");
+        assert_eq!(prune("if (true) a = 1 else b = 2;\nc = 3;"), "a = 1;\nc = 3;");
+        assert_eq!(prune("if (false) print(\"a\") else print(\"b\");"), "print(\"b\");");
+    }
+
+    #[test]
+    fn follows_else_if_chains() {
+        assert_eq!(prune("if (false) a = 1; else if (true) b = 2; else c = 3;"), "b = 2;");
+        assert_eq!(prune("if (false) a = 1; else if (false) b = 2; else c = 3;"), "c = 3;");
+        assert_eq!(prune("if (true) a = 1; else if (x) b = 2;"), "a = 1;");
+    }
+
+    #[test]
+    fn keeps_substatements_intact() {
+        assert_eq!(prune("while (x) if (false) a = 1;"), "while (x) {}");
+        assert_eq!(prune("if (x) if (true) { a = 1; }"), "if (x) { a = 1; }");
+        assert_eq!(prune("if (x) a = 1; else if (false) b = 2;"), "if (x) a = 1; else {}");
+    }
+
+    #[test]
+    fn leaves_uncertain_statements_alone() {
+        for src in ["if (x) a = 1;", "if (true) a = 1\n", "if (true) == b;", "if (true) a = 1 else b = 2\n"] {
+            assert_eq!(prune(src), src);
+        }
+    }
+}
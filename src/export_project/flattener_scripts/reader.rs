@@ -1,11 +1,11 @@
 use crate::export_project::flattener_scripts as flt;
 use flt::token::Token;
 use flt::token::Token::*;
-use std::collections::HashMap;
+use flt::constant_table::Constant;
 
-pub fn get_constants_map(source: &Vec<Token>) -> Result<HashMap<String, String>, String> {
+pub fn read_constants(source: &[Token], source_name: &str) -> Result<Vec<Constant>, String> {
 
-    let mut map = HashMap::new();
+    let mut constants = Vec::new();
     let iter = source.iter();
 
     // State 0: seeking binding or ignore declaration
@@ -15,11 +15,26 @@ pub fn get_constants_map(source: &Vec<Token>) -> Result<HashMap<String, String>,
     let mut ignoring = false;
     let mut bracket_depth = 0;
     let mut key = String::new();
-    let mut value = String::new();
-    let mut needs_space = false;
+    let mut value = Vec::new();
     let mut can_return = false;
 
+    // For definition metadata
+    let mut line = 1;
+    let mut key_line = 1;
+    let mut doc_comment: Vec<String> = Vec::new(); // comment block directly above the current statement
+    let mut trailing_comment = None;
+    let mut line_has_code = false;
+    let mut line_has_comment = false;
+    let mut last_defined_on_line = false; // whether a definition ended earlier on this line
+    let mut keep_next = false; // whether the next definition was marked with a keep directive
+
     for t in iter {
+        line += t.line_breaks();
+        match t {
+            NewLine | Whitespace(_) | ShortComment(_) | LongComment(_) | Keep => (),
+            _ => line_has_code = true,
+        }
+
         if ignoring { match t {
             IgnoreEnd => ignoring = false,
             _ => (),
@@ -27,9 +42,14 @@ pub fn get_constants_map(source: &Vec<Token>) -> Result<HashMap<String, String>,
         else { match t {
             NewLine => {
                 if mapper_state == 2 && can_return {
-                    map.insert(key.clone(), value.clone()); // value is guaranteed to be non-empty
+                    let comment = take_comment(&mut doc_comment, &mut trailing_comment);
+                    constants.push(make_constant(&key, value.clone(), source_name, key_line, comment, &mut keep_next)); // value is guaranteed to be non-empty
                     mapper_state = 0;
                 }
+                if line_has_code || !line_has_comment { doc_comment.clear() };
+                line_has_code = false;
+                line_has_comment = false;
+                last_defined_on_line = false;
             },
 
             Semicolon => {
@@ -38,8 +58,10 @@ pub fn get_constants_map(source: &Vec<Token>) -> Result<HashMap<String, String>,
                     1 => return Err(format!("Unexpected semicolon in assignment for {}", key)),
                     2 => {
                         if can_return {
-                            map.insert(key.clone(), value.clone()); // value is guaranteed to be non-empty
+                            let comment = take_comment(&mut doc_comment, &mut trailing_comment);
+                            constants.push(make_constant(&key, value.clone(), source_name, key_line, comment, &mut keep_next)); // value is guaranteed to be non-empty
                             mapper_state = 0;
+                            last_defined_on_line = true;
                         } else {
                             return Err(format!("Unexpected semicolon in assignment for {}", key));
                         }
@@ -54,7 +76,8 @@ pub fn get_constants_map(source: &Vec<Token>) -> Result<HashMap<String, String>,
                     1 => return Err(format!("Unexpected begin-ignore declaration in assignment for {}", key)),
                     2 => {
                         if can_return {
-                            map.insert(key.clone(), value.clone()); // value is guaranteed to be non-empty
+                            let comment = take_comment(&mut doc_comment, &mut trailing_comment);
+                            constants.push(make_constant(&key, value.clone(), source_name, key_line, comment, &mut keep_next)); // value is guaranteed to be non-empty
                             mapper_state = 0;
                         } else {
                             return Err(format!("Unexpected begin-ignore declaration in assignment for {}", key));
@@ -65,32 +88,63 @@ pub fn get_constants_map(source: &Vec<Token>) -> Result<HashMap<String, String>,
             },
 
             IgnoreEnd => return Err(String::from("Reached unpaired end-ignore declaration")),
+
+            // Applies to a definition on the same line, or else the next definition
+            Keep => {
+                match mapper_state {
+                    0 => {
+                        if last_defined_on_line {
+                            if let Some(c) = constants.last_mut() { c.keep = true };
+                        } else {
+                            keep_next = true;
+                        }
+                    }
+                    1 => return Err(format!("Unexpected keep declaration in assignment for {}", key)),
+                    2 => keep_next = true,
+                    _ => panic!("Invalid state reached"),
+                };
+                line_has_comment = true;
+            },
+
             DeformatBegin |
             DeformatEnd => return Err(String::from("Deformatting is not supported in the RCF user_event")),
+            NoFlatten |
+            NoFlattenBegin |
+            NoFlattenEnd => return Err(String::from("Flatten opt-outs are not supported in the RCF user_event")),
+            IfBegin(_) |
+            Else |
+            IfEnd => return Err(String::from("Conditional declarations must be resolved before reading constants")),
 
             Whitespace(_) => (),
-            LongComment(_) => (),
-            ShortComment(_) => (),
+            LongComment(s) | ShortComment(s) => {
+                let text = strip_comment(s);
+                if mapper_state != 0 || last_defined_on_line {
+                    // Comment trails a definition on the same line
+                    if trailing_comment.is_none() && !text.is_empty() {
+                        trailing_comment = Some(text);
+                    }
+                    if last_defined_on_line {
+                        if let Some(c) = constants.last_mut() {
+                            if c.comment.is_none() { c.comment = trailing_comment.take() };
+                        }
+                    }
+                } else if !line_has_code && !text.is_empty() {
+                    doc_comment.push(text);
+                }
+                line_has_comment = true;
+            },
 
             Identifier(s) => {
                 match mapper_state {
                     0 => {
                         key = String::from(s);
+                        key_line = line;
                         mapper_state = 1;
                     }
                     1 => return Err(format!("Expected '=', got identifier '{}'", s)),
                     2 => {
-                        let result = map.get(s);
-                        if let Some(r) = result {
-                            if needs_space { value.push(' ') };
-                            value.push_str(&r);
-                            needs_space = true;
-                            can_return = bracket_depth == 0;
-                        } else {
-                            value.push_str(&s);
-                            needs_space = true;
-                            can_return = bracket_depth == 0;
-                        }
+                        value.push(t.clone());
+                        can_return = bracket_depth == 0;
                     } 
                     _ => panic!("Invalid state reached"),
                 };
@@ -100,8 +154,8 @@ pub fn get_constants_map(source: &Vec<Token>) -> Result<HashMap<String, String>,
                 match mapper_state {
                     1 => {
                         mapper_state = 2;
-                        value = String::new();
-                        needs_space = false;
+                        value = Vec::new();
+                        trailing_comment = None;
                         can_return = false;
                         bracket_depth = 0;
                     }
@@ -114,9 +168,7 @@ pub fn get_constants_map(source: &Vec<Token>) -> Result<HashMap<String, String>,
                     0 => return Err(format!("Expected start statement, got literal '{}'", s)),
                     1 => return Err(format!("Expected '=', got literal {}", s)),
                     2 => {
-                        if needs_space { value.push(' ') };
-                        value.push_str(&s);
-                        needs_space = true;
+                        value.push(t.clone());
                         can_return = bracket_depth == 0;
                     },
                     _ => panic!("Invalid state reached"),
@@ -128,8 +180,7 @@ pub fn get_constants_map(source: &Vec<Token>) -> Result<HashMap<String, String>,
                     0 => return Err(format!("Expected start statement, got symbol '{}'", s)),
                     1 => return Err(format!("Expected '=', got symbol '{}'", s)),
                     2 => {
-                        value.push_str(&s);
-                        needs_space = false;
+                        value.push(t.clone());
                         can_return = false; // this may not be an accurate heuristic - need to check
                     },
                     _ => panic!("Invalid state reached"),
@@ -141,8 +192,7 @@ pub fn get_constants_map(source: &Vec<Token>) -> Result<HashMap<String, String>,
                     0 => return Err(String::from("Expected start statement, got '.'")),
                     1 => return Err(String::from("Expected '=', got '.'")),
                     2 => {
-                        value.push_str(".");
-                        needs_space = false;
+                        value.push(t.clone());
                         can_return = false; // this may not be an accurate heuristic - need to check
                     },
                     _ => panic!("Invalid state reached"),
@@ -154,8 +204,7 @@ pub fn get_constants_map(source: &Vec<Token>) -> Result<HashMap<String, String>,
                     0 => return Err(format!("Expected start statement, got open bracket '{}'", s)),
                     1 => return Err(format!("Expected '=', got open bracket '{}'", s)),
                     2 => {
-                        value.push_str(&s);
-                        needs_space = false;
+                        value.push(t.clone());
                         can_return = false;
                         bracket_depth += 1;
                     },
@@ -173,8 +222,7 @@ pub fn get_constants_map(source: &Vec<Token>) -> Result<HashMap<String, String>,
                     2 => {
                         if bracket_depth <= 0 { return Err(format!("Reached unpaired close bracket {}", s)) }
                         bracket_depth -= 1;
-                        value.push_str(&s);
-                        needs_space = false;
+                        value.push(t.clone());
                         can_return = bracket_depth == 0;
                     },
                     _ => panic!("Invalid state reached"),
@@ -190,7 +238,8 @@ pub fn get_constants_map(source: &Vec<Token>) -> Result<HashMap<String, String>,
         1 => return Err(format!("Unexpected end-of-file in assignment for {}", key)),
         2 => {
             if can_return {
-                map.insert(key.clone(), value.clone()); // value is guaranteed to be non-empty
+                let comment = take_comment(&mut doc_comment, &mut trailing_comment);
+                constants.push(make_constant(&key, value, source_name, key_line, comment, &mut keep_next)); // value is guaranteed to be non-empty
             } else {
                 return Err(format!("Unexpected end-of-file in assignment for {}", key));
             }
@@ -198,6 +247,32 @@ pub fn get_constants_map(source: &Vec<Token>) -> Result<HashMap<String, String>,
         _ => panic!("Invalid state reached"),
     };
 
-    Ok(map)
+    Ok(constants)
+
+}
+
+fn make_constant(key: &str, value: Vec<Token>, source_name: &str, line: usize, comment: Option<String>, keep_next: &mut bool) -> Constant {
+    let mut c = Constant::new(key, value, source_name, line, comment);
+    c.keep = *keep_next;
+    *keep_next = false;
+    c
+}
+
+// Prefers the comment block above a definition, falling back on a trailing comment.
+fn take_comment(doc_comment: &mut Vec<String>, trailing_comment: &mut Option<String>) -> Option<String> {
+    let comment = if !doc_comment.is_empty() {
+        Some(doc_comment.join("\n"))
+    } else {
+        trailing_comment.take()
+    };
+    doc_comment.clear();
+    *trailing_comment = None;
+    comment
+}
 
+fn strip_comment(s: &str) -> String {
+    let s = s.strip_prefix("//").unwrap_or(s);
+    let s = s.strip_prefix("/*").unwrap_or(s);
+    let s = s.strip_suffix("*/").unwrap_or(s);
+    String::from(s.trim())
 }
\ No newline at end of file
@@ -128,6 +128,13 @@ impl RcfScanner {
                     "//#RCFENDIGNORE" => Token::IgnoreEnd,
                     "//#RCFBEGINDEFORMAT" => Token::DeformatBegin,
                     "//#RCFENDDEFORMAT" => Token::DeformatEnd,
+                    "//#RCFKEEP" => Token::Keep,
+                    "//#RCFNOFLATTEN" => Token::NoFlatten,
+                    "//#RCFBEGINNOFLATTEN" => Token::NoFlattenBegin,
+                    "//#RCFENDNOFLATTEN" => Token::NoFlattenEnd,
+                    "//#RCFELSE" => Token::Else,
+                    "//#RCFENDIF" => Token::IfEnd,
+                    c if c.starts_with("//#RCFIF ") => Token::IfBegin(String::from(&c[9..])),
                     c => {
                         let mut out = String::from(c);
                         out.push_str(&whitespace);
@@ -10,6 +10,13 @@ pub enum Token {
     IgnoreEnd,
     DeformatBegin,
     DeformatEnd,
+    Keep,
+    NoFlatten,
+    NoFlattenBegin,
+    NoFlattenEnd,
+    IfBegin(String), // contains the condition
+    Else,
+    IfEnd,
     Symbol(String),
     OpenBracket(String),
     CloseBracket(String),
@@ -18,4 +25,57 @@ pub enum Token {
     ShortComment(String), // Distinguished by its reliance on newline characters
     LongComment(String),
     Whitespace(String), // Since this is meant to preserve formatting
+}
+
+impl Token {
+
+    // Reproduces the source text that the token was scanned from.
+    pub fn to_source(&self) -> String {
+        match self {
+            Token::Dot => String::from("."),
+            Token::Equal => String::from("="),
+            Token::NewLine => String::from("\n"),
+            Token::Semicolon => String::from(";"),
+            Token::IgnoreBegin => String::from("//#RCFBEGINIGNORE"),
+            Token::IgnoreEnd => String::from("//#RCFENDIGNORE"),
+            Token::DeformatBegin => String::from("//#RCFBEGINDEFORMAT"),
+            Token::DeformatEnd => String::from("//#RCFENDDEFORMAT"),
+            Token::Keep => String::from("//#RCFKEEP"),
+            Token::NoFlatten => String::from("//#RCFNOFLATTEN"),
+            Token::NoFlattenBegin => String::from("//#RCFBEGINNOFLATTEN"),
+            Token::NoFlattenEnd => String::from("//#RCFENDNOFLATTEN"),
+            Token::IfBegin(s) => format!("//#RCFIF {}", s),
+            Token::Else => String::from("//#RCFELSE"),
+            Token::IfEnd => String::from("//#RCFENDIF"),
+            Token::Symbol(s) |
+            Token::OpenBracket(s) |
+            Token::CloseBracket(s) |
+            Token::Literal(s) |
+            Token::Identifier(s) |
+            Token::ShortComment(s) |
+            Token::LongComment(s) |
+            Token::Whitespace(s) => s.clone(),
+        }
+    }
+
+    // Whether the token only affects formatting, such as whitespace, line breaks and comments.
+    pub fn is_formatting(&self) -> bool {
+        matches!(self, Token::Whitespace(_) | Token::NewLine | Token::ShortComment(_) | Token::LongComment(_))
+    }
+
+    // Whether the token is one of GML's operators that are spelled as words (e.g. and, div).
+    pub fn is_word_operator(&self) -> bool {
+        matches!(self, Token::Identifier(s) if matches!(s.as_str(), "and" | "or" | "xor" | "not" | "div" | "mod"))
+    }
+
+    // Number of line breaks contained in the token.
+    // Used to track line numbers, since tokens don't store their own positions.
+    pub fn line_breaks(&self) -> usize {
+        match self {
+            Token::NewLine => 1,
+            Token::LongComment(s) | Token::Literal(s) => s.matches('\n').count(),
+            _ => 0,
+        }
+    }
+
 }
\ No newline at end of file
@@ -0,0 +1,240 @@
+use crate::export_project::flattener_scripts as flt;
+use flt::token::Token;
+use flt::token::Token::*;
+use flt::constant_table::ConstantTable;
+use flt::locals::find_shadowing_locals;
+use flt::expression::matching_bracket;
+
+// Finds every statement in a script that modifies a constant, returning one error per write.
+// Writes through accessor chains (e.g. player_id.CONSTANT += 1) are also caught.
+pub fn find_constant_writes(ts: &[Token], table: &ConstantTable, file_name: &str) -> Vec<String> {
+    let mut errors = Vec::new();
+    let mut line = 1;
+    let mut ignoring = false;
+
+    // Scripts or regions that opt out of flattening may freely modify the live variables
+    let noflatten_file = ts.contains(&NoFlatten);
+    let mut noflatten = noflatten_file;
+
+    // Locals that share a constant's name aren't flattened, so they can be written to
+    let shadowing = find_shadowing_locals(ts, table);
+
+    for (idx, t) in ts.iter().enumerate() {
+        line += t.line_breaks();
+        match t {
+            IgnoreBegin => ignoring = true,
+            IgnoreEnd => ignoring = false,
+            NoFlattenBegin => noflatten = true,
+            NoFlattenEnd => noflatten = noflatten_file,
+            Identifier(s) if !ignoring && !noflatten => {
+                if !table.is_flattened(s) || shadowing.shadowed.contains(&idx) { continue };
+                if let Some(kind) = get_write_kind(ts, idx) {
+                    errors.push(format!(
+                        "{}:{}: Constant {} is {}, but constants must not change after being defined",
+                        file_name, line, s, kind,
+                    ));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    errors
+}
+
+// Describes how the identifier at idx is written to, if it's the target of a write.
+pub fn get_write_kind(ts: &[Token], idx: usize) -> Option<&'static str> {
+    // Step back to the start of any accessor chain
+    let mut start = idx;
+    while start >= 2 && ts[start-1] == Dot && matches!(ts[start-2], Identifier(_)) {
+        start -= 2;
+    }
+
+    // Prefix increment and decrement
+    if start >= 2 {
+        match (&ts[start-2], &ts[start-1]) {
+            (Symbol(a), Symbol(b)) if a == "+" && b == "+" => return Some("incremented"),
+            (Symbol(a), Symbol(b)) if a == "-" && b == "-" => return Some("decremented"),
+            _ => (),
+        }
+    }
+
+    // Step over any index into the constant (e.g. CONSTANT[0] = 1)
+    let mut end = idx;
+    while let Some(n) = next_code(ts, end + 1) {
+        match &ts[n] {
+            OpenBracket(s) if s == "[" => match matching_bracket(ts, n) {
+                Some(close) => end = close,
+                None => break,
+            },
+            _ => break,
+        }
+    }
+
+    // Assignments may continue on the next line, but postfix operators can't
+    let next = next_code(ts, end + 1);
+    match next.map(|n| &ts[n]) {
+        Some(Equal) => {
+            if is_statement_start(ts, start) { Some("assigned to") }
+            else { None } // GML also allows '=' as a comparison
+        }
+        Some(Symbol(s)) => {
+            let n = next.unwrap();
+            let same_line = !ts[end+1..n].contains(&NewLine);
+            match (s.as_str(), ts.get(n+1)) {
+                ("+", Some(Symbol(b))) if b == "+" && same_line => Some("incremented"),
+                ("-", Some(Symbol(b))) if b == "-" && same_line => Some("decremented"),
+                ("+" | "-" | "*" | "/" | "%" | "&" | "|" | "^", Some(Equal)) => Some("modified by a compound assignment"),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+// Whether the token at idx begins a statement, judging by the tokens before it.
+pub fn is_statement_start(ts: &[Token], idx: usize) -> bool {
+    match previous_significant(ts, idx) {
+        None => true,
+        Some(p) => match &ts[p] {
+            NewLine | Semicolon => true,
+            // The initializer of a for loop is a statement of its own
+            OpenBracket(s) if s == "(" => previous_significant(ts, p).is_some_and(|f| ts[f] == Identifier(String::from("for"))),
+            OpenBracket(s) | CloseBracket(s) => s == "{" || s == "}" || s == ")",
+            Identifier(s) => matches!(s.as_str(), "else" | "do" | "then"),
+            Symbol(s) if s == ":" => is_case_label(ts, p),
+            _ => false,
+        },
+    }
+}
+
+// Whether the colon at idx ends a case or default label, rather than being part of a ternary.
+fn is_case_label(ts: &[Token], colon: usize) -> bool {
+    for t in ts[..colon].iter().rev() {
+        match t {
+            Identifier(s) if s == "case" || s == "default" => return true,
+            Symbol(s) if s == "?" || s == ":" => return false,
+            OpenBracket(s) | CloseBracket(s) if s == "{" || s == "}" => return false,
+            Semicolon | NewLine => return false,
+            _ => (),
+        }
+    }
+    false
+}
+
+pub fn next_significant(ts: &[Token], from: usize) -> Option<usize> {
+    (from..ts.len()).find(|&i| !is_insignificant(&ts[i]))
+}
+
+pub fn previous_significant(ts: &[Token], before: usize) -> Option<usize> {
+    (0..before).rev().find(|&i| !is_insignificant(&ts[i]))
+}
+
+// Like next_significant, but also skips line breaks.
+pub fn next_code(ts: &[Token], from: usize) -> Option<usize> {
+    (from..ts.len()).find(|&i| !ts[i].is_formatting())
+}
+
+// Whether the statement at idx is the body of another statement, such as an if or loop.
+pub fn is_substatement(ts: &[Token], idx: usize) -> bool {
+    let Some(p) = previous_code(ts, idx) else { return false };
+    match &ts[p] {
+        CloseBracket(s) if s == ")" => {
+            // Only the condition of a statement that takes a body, not something like foo()
+            let header = opening_bracket(ts, p).and_then(|open| previous_code(ts, open));
+            header.is_some_and(|h| matches!(&ts[h], Identifier(s) if matches!(s.as_str(), "if" | "while" | "for" | "with" | "repeat")))
+        }
+        Identifier(s) => matches!(s.as_str(), "else" | "do" | "then"),
+        _ => false,
+    }
+}
+
+fn previous_code(ts: &[Token], before: usize) -> Option<usize> {
+    (0..before).rev().find(|&i| !ts[i].is_formatting())
+}
+
+// Finds the bracket opened by the one closed at idx.
+fn opening_bracket(ts: &[Token], close: usize) -> Option<usize> {
+    let mut depth = 0;
+    for i in (0..=close).rev() {
+        match &ts[i] {
+            CloseBracket(_) => depth += 1,
+            OpenBracket(_) => {
+                depth -= 1;
+                if depth == 0 { return Some(i) };
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+fn is_insignificant(t: &Token) -> bool {
+    matches!(t, Whitespace(_) | ShortComment(_) | LongComment(_))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flt::scanner::RcfScanner;
+    use flt::read_constant_script;
+
+    fn writes(src: &str) -> Vec<String> {
+        let mut table = ConstantTable::new();
+        read_constant_script(&mut table, "WALK = 4;\nLIST = [1, 2];", "user_event3.gml", &[]).unwrap();
+        table.resolve().unwrap();
+        let mut s = RcfScanner::new(src);
+        find_constant_writes(&s.scan_tokens().unwrap(), &table, "test.gml")
+    }
+
+    #[test]
+    fn finds_assignments() {
+        assert_eq!(writes("WALK = 2;"), ["test.gml:1: Constant WALK is assigned to, but constants must not change after being defined"]);
+        assert_eq!(writes("a = 1;\nplayer_id.WALK = 2;").len(), 1);
+        assert_eq!(writes("for (WALK = 0; a < 2; a++) {}").len(), 1);
+        assert_eq!(writes("WALK\n    = 2;").len(), 1);
+        assert_eq!(writes("if (a) WALK = 2; else WALK = 3;").len(), 2);
+    }
+
+    #[test]
+    fn finds_other_kinds_of_writes() {
+        assert!(writes("WALK++;")[0].contains("incremented"));
+        assert!(writes("--WALK;")[0].contains("decremented"));
+        assert!(writes("WALK *= 2;")[0].contains("compound assignment"));
+        assert!(writes("LIST[0] = 1;")[0].contains("assigned to"));
+        assert!(writes("LIST[1][2] += 1;")[0].contains("compound assignment"));
+    }
+
+    #[test]
+    fn finds_writes_after_case_labels() {
+        assert_eq!(writes("switch (state) { case 1: WALK = 2; break; }").len(), 1);
+        assert_eq!(writes("switch (state) { case (a + 1): WALK = 2; break; default: WALK++; }").len(), 2);
+    }
+
+    #[test]
+    fn finds_substatements() {
+        let sub = |src: &str| {
+            let mut s = RcfScanner::new(src);
+            let ts = s.scan_tokens().unwrap();
+            let idx = ts.iter().position(|t| *t == Identifier(String::from("target"))).unwrap();
+            is_substatement(&ts, idx)
+        };
+        assert!(sub("if (a) target();"));
+        assert!(sub("while (f(a))\n    target();"));
+        assert!(sub("if (a) b = 1; else target();"));
+        assert!(!sub("foo()\ntarget();"));
+        assert!(!sub("user_event(3) // load\ntarget();"));
+        assert!(!sub("if (a) { b(); }\ntarget();"));
+        assert!(!sub("target();"));
+    }
+
+    #[test]
+    fn ignores_reads_and_comparisons() {
+        assert!(writes("a = WALK;").is_empty());
+        assert!(writes("if (WALK = 4) a = 1;").is_empty());
+        assert!(writes("a = b ? c : WALK = 4;").is_empty());
+        assert!(writes("a = LIST[0];").is_empty());
+        assert!(writes("b = WALK\n++c;").is_empty());
+        assert!(writes("var WALK = 2;").is_empty());
+    }
+}
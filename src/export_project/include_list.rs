@@ -1,142 +1,186 @@
-use std::collections::HashMap;
+mod glob;
+
 use std::vec::Vec;
 
-#[derive(Debug)]
-enum AllowType {
-    Folder(Box<IncludeList>),
-    Type(String),
-    File(String),
-}
+use crate::export_project as rcf;
+use rcf::include_list::glob::Glob;
 
-impl AllowType {
-    // Panics if called on a folder!
-    pub fn unsafe_to_str(&self) -> &str {
-        match self {
-            Folder(_) => panic!("Cannot call on folders."),
-            Type(s) => &s,
-            File(s) => &s,
-        }
-    }
+#[derive(Debug)]
+struct IncludeRule {
+    pattern: Glob,
+    exclude: bool,
+    entry: String,
+    line: Option<usize>, // None for entries pulled in by @default
 }
 
-use crate::export_project as rcf;
-use rcf::include_list::AllowType::*;
-
+// Each entry is a glob pattern, relative to the project root.
+// Entries that match a folder include everything inside it.
+// Entries starting with '!' exclude files instead, and later entries take precedence over earlier ones.
 #[derive(Debug)]
 pub struct IncludeList {
-    contents: HashMap<String, AllowType>,
+    lines: Vec<String>, // the include file as written, comments included
+    default: String,
+    rules: Vec<IncludeRule>,
+    ignore_case: bool,
 }
 
 impl IncludeList {
 
-    pub fn new() -> IncludeList {
-        IncludeList{ contents: HashMap::new() }
+    // The default entries are inserted wherever the include file uses the @default directive.
+    pub fn construct(raw_contents: &str, default: &str) -> Result<IncludeList, String> {
+        let lines = raw_contents.lines().map(|l| String::from(l.trim_end())).collect();
+        let mut incl = IncludeList{ lines, default: String::from(default), rules: Vec::new(), ignore_case: false };
+        incl.rebuild_rules()?;
+        Ok(incl)
     }
 
-    pub fn construct(raw_contents: &str) -> Result<IncludeList, String> {
-        let mut incl = IncludeList{ contents: HashMap::new() };
-        incl.add_entries(raw_contents)?;
-        Ok(incl)
+    // Writes the list back out in the include file format.
+    // Comments and blank lines are kept, so construct(serialize()) gives back the same list.
+    pub fn serialize(&self) -> String {
+        let mut out = self.lines.join("\n");
+        out.push('\n');
+        out
     }
 
-    pub fn add_entries(&mut self, raw_contents: &str) -> Result<(), String> {
-        let mut path = Vec::new();
-        let mut entry = String::new();
-        let mut is_wildcard = false;
-        let mut wildcard_valid = false;
-
-        for c in raw_contents.chars() {
-            match c {
-                '/' | '\\' => {
-                    if is_wildcard {
-                        return Err(String::from("Encountered '*' in folder name"));
-                    }
-                    path.push(entry);
-                    entry = String::new();
-                }
-                '\n' => {
-                    if entry != "" {
-                        if is_wildcard {
-                            if wildcard_valid {
-                                self.add_entry_inner(&path, Type(entry));
-                            } else {
-                                return Err(String::from("Encountered improper use of '*'"));
-                            }
-                        } else {
-                            self.add_entry_inner(&path, File(entry));
-                        }
-                        path = Vec::new();
-                        entry = String::new();
-                        is_wildcard = false;
-                        wildcard_valid = false;
-                    }
-                }
-                '\r' => (), // omit carriage returns
-                '*' => {
-                    if entry != "" || is_wildcard { 
-                        return Err(String::from("Encountered improper use of '*'"));
-                    }
-                    is_wildcard = true;
-                }
-                '.' => {
-                    if entry != "" && is_wildcard { 
-                        return Err(String::from("Encountered improper use of '*'"));
-                    }
-                    wildcard_valid = is_wildcard;
-                    entry.push(c);
-                }
-                _ => {
-                    entry.push(c);
-                }
-            }
+    // Lists every entry and directive, in order, without comments or blank lines.
+    pub fn entries(&self) -> Vec<&str> {
+        self.lines.iter()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .collect()
+    }
+
+    // Appends an entry (or exclusion) to the end of the list, where it takes precedence over earlier entries.
+    pub fn add_entry(&mut self, entry: &str) -> Result<(), String> {
+        let entry = entry.trim();
+        if entry.is_empty() || entry.starts_with('#') {
+            return Err(format!("'{}' is not a valid include entry", entry));
+        }
+        if self.entries().contains(&entry) {
+            return Err(format!("Include list already contains '{}'", entry));
+        }
+
+        self.lines.push(String::from(entry));
+        if let Err(e) = self.rebuild_rules() {
+            self.lines.pop();
+            return Err(e);
         }
-        
         Ok(())
     }
 
-    fn add_entry_inner(&mut self, path: &[String], entry: AllowType) {
-        let len = path.len();
-        match len {
-            0 => {
-                // No Folder-type objects should ever be saved to entry.
-                // As such, safety should be guaranteed by internal conditions.
-                self.contents.insert(
-                    String::from(entry.unsafe_to_str()),
-                    entry,
-                );
-            },
-            _ => {
-                let cur = &path[0];
-                if let Some(Folder(f)) = self.contents.get_mut(cur) {
-                    f.add_entry_inner(&path[1..path.len()], entry);
-                } else {
-                    let mut f = IncludeList::new();
-                    f.add_entry_inner(&path[1..path.len()], entry);
-                    self.contents.insert(
-                        String::from(cur),
-                        Folder(Box::new(f)),
-                    );
-                }
-            },
+    // Removes every line containing exactly the given entry.
+    pub fn remove_entry(&mut self, entry: &str) -> Result<(), String> {
+        let entry = entry.trim();
+        let len = self.lines.len();
+        self.lines.retain(|l| l.trim() != entry);
+        if self.lines.len() == len {
+            return Err(format!("Include list does not contain '{}'", entry));
+        }
+        self.rebuild_rules()
+    }
+
+    fn rebuild_rules(&mut self) -> Result<(), String> {
+        self.rules = Vec::new();
+        add_rules(&mut self.rules, &self.lines.join("\n"), &self.default)
+    }
+
+    pub fn set_ignore_case(&mut self, ignore_case: bool) {
+        self.ignore_case = ignore_case;
+    }
+
+    // Whether the file at the given path (relative to the project root) should be exported.
+    pub fn file_included(&self, path: &str) -> bool {
+        self.file_matches(path, self.ignore_case)
+    }
+
+    // Whether any file inside the given folder could be exported, so that it's worth visiting.
+    pub fn folder_included(&self, path: &str) -> bool {
+        self.folder_matches(path, self.ignore_case)
+    }
+
+    // Whether a path's inclusion depends on letter case (e.g. Sprites/Idle.PNG against sprites/*.png).
+    pub fn case_mismatch(&self, path: &str, is_folder: bool) -> bool {
+        if is_folder { self.folder_matches(path, true) != self.folder_matches(path, false) }
+        else { self.file_matches(path, true) != self.file_matches(path, false) }
+    }
+
+    // Lists the entries (and their line numbers) that don't match any of the given paths.
+    // Entries from @default are skipped, since they cover files that not every project has.
+    pub fn unmatched_entries(&self, paths: &[String]) -> Vec<(usize, &str)> {
+        self.rules.iter()
+            .filter_map(|r| r.line.map(|line| (line, r)))
+            .filter(|(_, r)| !paths.iter().any(|p| r.pattern.matches_within(p, self.ignore_case)))
+            .map(|(line, r)| (line, r.entry.as_str()))
+            .collect()
+    }
+
+    fn file_matches(&self, path: &str, ignore_case: bool) -> bool {
+        self.rules.iter().rev()
+            .find(|r| r.pattern.matches_within(path, ignore_case))
+            .is_some_and(|r| !r.exclude)
+    }
+
+    fn folder_matches(&self, path: &str, ignore_case: bool) -> bool {
+        // Entries after the last one covering the whole folder may still pick out files inside it
+        let start = match self.rules.iter().rposition(|r| r.pattern.matches_within(path, ignore_case)) {
+            Some(idx) if !self.rules[idx].exclude => return true,
+            Some(idx) => idx + 1,
+            None => 0,
         };
+        self.rules[start..].iter().any(|r| !r.exclude && r.pattern.may_match_inside(path, ignore_case))
     }
 
-    pub fn get_folder(&self, f: &str) -> Option<&Box<IncludeList>> {
-        if let Some(Folder(incl)) = self.contents.get(f) {
-            Some(&incl)
-        } else {
-            None
+}
+
+fn add_rules(rules: &mut Vec<IncludeRule>, raw_contents: &str, default: &str) -> Result<(), String> {
+    for (idx, line) in raw_contents.lines().enumerate() {
+        let entry = line.trim();
+        if entry.is_empty() || entry.starts_with('#') { continue };
+        if entry.starts_with('@') {
+            match entry {
+                "@default" => {
+                    let first = rules.len();
+                    add_rules(rules, default, "")?;
+                    rules[first..].iter_mut().for_each(|r| r.line = None);
+                }
+                _ => return Err(format!("Unknown include directive '{}' (line {})", entry, idx + 1)),
+            }
+            continue;
+        }
+        let (exclude, pattern) = match entry.strip_prefix('!') {
+            Some(p) => (true, p.trim_start()),
+            None => (false, entry),
+        };
+        match Glob::new(pattern) {
+            Ok(pattern) => rules.push(IncludeRule{ pattern, exclude, entry: String::from(entry), line: Some(idx + 1) }),
+            Err(e) => return Err(format!("{} (include entry '{}', line {})", e, entry, idx + 1)),
         }
     }
 
-    pub fn file_included(&self, f: &str) -> bool {
-        if let Some(File(_)) = self.contents.get(f) { true }
-        else { false }
-    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    pub fn type_included(&self, f: &str) -> bool {
-        if let Some(Type(_)) = self.contents.get(f) { true }
-        else { false }
+    #[test]
+    fn later_entries_take_precedence() {
+        let incl = IncludeList::construct("# comment\nscripts\n!scripts/debug*.gml\nscripts/debug_keep.gml\n", "").unwrap();
+        assert!(incl.file_included("scripts/init.gml"));
+        assert!(!incl.file_included("scripts/debug_menu.gml"));
+        assert!(incl.file_included("scripts/debug_keep.gml"));
+        assert!(!incl.file_included("sprites/idle.png"));
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn visits_excluded_folders_with_included_files() {
+        let incl = IncludeList::construct("!sprites\nsprites/idle.png\n", "").unwrap();
+        assert!(incl.folder_included("sprites"));
+        assert!(!incl.file_included("sprites/hurt.png"));
+        assert!(incl.file_included("sprites/idle.png"));
+
+        let incl = IncludeList::construct("sprites\n!sprites\n", "").unwrap();
+        assert!(!incl.folder_included("sprites"));
+    }
+}
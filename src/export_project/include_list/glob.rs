@@ -0,0 +1,198 @@
+// A path pattern from the include file, matched one path segment at a time.
+// Supports '*' and '?' within a segment, character classes ("[abc]", "[a-z]", "[!abc]")
+// and "**" as a whole segment, which matches any number of folders.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    segments: Vec<Vec<char>>,
+    folded: Vec<Vec<char>>, // lowercase copy for case-insensitive matching
+}
+
+impl Glob {
+
+    pub fn new(pattern: &str) -> Result<Glob, String> {
+        let segments: Vec<Vec<char>> = split_path(pattern).iter()
+            .map(|s| s.chars().collect())
+            .collect();
+        if segments.is_empty() {
+            return Err(String::from("Encountered empty pattern"));
+        }
+        for s in segments.iter() {
+            validate_segment(s)?;
+        }
+        let folded = segments.iter().map(|s| s.iter().flat_map(|c| c.to_lowercase()).collect()).collect();
+        Ok(Glob{ segments, folded })
+    }
+
+    // Whether the pattern matches the path or one of the folders containing it.
+    pub fn matches_within(&self, path: &str, ignore_case: bool) -> bool {
+        let path = to_segments(path, ignore_case);
+        (1..=path.len()).any(|n| match_segments(self.get_segments(ignore_case), &path[..n]))
+    }
+
+    // Whether the pattern could match anything inside the given folder.
+    pub fn may_match_inside(&self, dir: &str, ignore_case: bool) -> bool {
+        match_prefix(self.get_segments(ignore_case), &to_segments(dir, ignore_case))
+    }
+
+    fn get_segments(&self, ignore_case: bool) -> &[Vec<char>] {
+        if ignore_case { &self.folded } else { &self.segments }
+    }
+
+}
+
+pub fn split_path(path: &str) -> Vec<&str> {
+    path.split(['/', '\\']).filter(|s| !s.is_empty()).collect()
+}
+
+fn to_segments(path: &str, ignore_case: bool) -> Vec<Vec<char>> {
+    split_path(path).iter()
+        .map(|s| if ignore_case { s.chars().flat_map(|c| c.to_lowercase()).collect() } else { s.chars().collect() })
+        .collect()
+}
+
+fn validate_segment(s: &[char]) -> Result<(), String> {
+    let mut idx = 0;
+    while idx < s.len() {
+        if s[idx] == '[' {
+            match class_end(s, idx) {
+                Some(end) => idx = end,
+                None => return Err(String::from("Encountered unclosed '[' in pattern")),
+            }
+        }
+        idx += 1;
+    }
+    Ok(())
+}
+
+fn match_segments(pat: &[Vec<char>], path: &[Vec<char>]) -> bool {
+    match pat.first() {
+        None => path.is_empty(),
+        Some(p) if is_globstar(p) => (0..=path.len()).any(|n| match_segments(&pat[1..], &path[n..])),
+        Some(p) => !path.is_empty() && match_segment(p, &path[0]) && match_segments(&pat[1..], &path[1..]),
+    }
+}
+
+// Whether some path inside dir could match the remaining pattern.
+fn match_prefix(pat: &[Vec<char>], dir: &[Vec<char>]) -> bool {
+    match pat.first() {
+        None => false,
+        Some(p) if is_globstar(p) => true,
+        Some(_) if dir.is_empty() => true,
+        Some(p) => match_segment(p, &dir[0]) && match_prefix(&pat[1..], &dir[1..]),
+    }
+}
+
+fn is_globstar(p: &[char]) -> bool {
+    p == ['*', '*']
+}
+
+fn match_segment(pat: &[char], name: &[char]) -> bool {
+    match pat.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|n| match_segment(&pat[1..], &name[n..])),
+        Some('?') => !name.is_empty() && match_segment(&pat[1..], &name[1..]),
+        Some('[') => {
+            let end = match class_end(pat, 0) {
+                Some(end) => end,
+                None => return false,
+            };
+            !name.is_empty() && class_matches(&pat[1..end], name[0]) && match_segment(&pat[end+1..], &name[1..])
+        }
+        Some(c) => name.first() == Some(c) && match_segment(&pat[1..], &name[1..]),
+    }
+}
+
+// Finds the closing bracket of the character class opened at idx.
+// A ']' straight after the opening bracket (or its negation) is treated as a member of the class.
+fn class_end(s: &[char], idx: usize) -> Option<usize> {
+    let mut start = idx + 1;
+    if matches!(s.get(start), Some('!') | Some('^')) { start += 1 };
+    (start + 1..s.len()).find(|&i| s[i] == ']')
+}
+
+fn class_matches(class: &[char], c: char) -> bool {
+    let (negated, class) = match class.first() {
+        Some('!') | Some('^') => (true, &class[1..]),
+        _ => (false, class),
+    };
+
+    let mut found = false;
+    let mut idx = 0;
+    while idx < class.len() {
+        if idx + 2 < class.len() && class[idx+1] == '-' {
+            if class[idx] <= c && c <= class[idx+2] { found = true };
+            idx += 3;
+        } else {
+            if class[idx] == c { found = true };
+            idx += 1;
+        }
+    }
+
+    found != negated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        Glob::new(pattern).unwrap().matches_within(path, false)
+    }
+
+    #[test]
+    fn matches_wildcards_within_a_segment() {
+        assert!(matches("scripts/*.gml", "scripts/init.gml"));
+        assert!(!matches("scripts/*.gml", "scripts/attacks/jab.gml"));
+        assert!(matches("sprites/idle_?.png", "sprites/idle_1.png"));
+        assert!(!matches("sprites/idle_?.png", "sprites/idle_10.png"));
+    }
+
+    #[test]
+    fn matches_globstar_across_folders() {
+        assert!(matches("**/*.gml", "init.gml"));
+        assert!(matches("**/*.gml", "scripts/attacks/jab.gml"));
+        assert!(matches("scripts/**/jab.gml", "scripts/jab.gml"));
+        assert!(matches("scripts/**/jab.gml", "scripts/attacks/old/jab.gml"));
+        assert!(!matches("scripts/**/jab.gml", "sprites/jab.gml"));
+    }
+
+    #[test]
+    fn matches_character_classes() {
+        assert!(matches("hurt[12].png", "hurt1.png"));
+        assert!(!matches("hurt[12].png", "hurt3.png"));
+        assert!(matches("hurt[0-9].png", "hurt7.png"));
+        assert!(matches("hurt[!0-9].png", "hurtx.png"));
+        assert!(!matches("hurt[^0-9].png", "hurt7.png"));
+        assert!(matches("a[]]b", "a]b"));
+    }
+
+    #[test]
+    fn matches_files_inside_matched_folders() {
+        assert!(matches("sounds", "sounds/hit.ogg"));
+        assert!(matches("sounds\\", "sounds/sfx/hit.ogg"));
+        assert!(!matches("sounds", "soundsextra/hit.ogg"));
+    }
+
+    #[test]
+    fn ignores_case_when_asked() {
+        let g = Glob::new("sprites/*.png").unwrap();
+        assert!(!g.matches_within("Sprites/Idle.PNG", false));
+        assert!(g.matches_within("Sprites/Idle.PNG", true));
+    }
+
+    #[test]
+    fn finds_folders_worth_visiting() {
+        let g = Glob::new("scripts/attacks/*.gml").unwrap();
+        assert!(g.may_match_inside("scripts", false));
+        assert!(g.may_match_inside("scripts/attacks", false));
+        assert!(!g.may_match_inside("sprites", false));
+        assert!(Glob::new("**/*.gml").unwrap().may_match_inside("anything/at/all", false));
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        assert!(Glob::new("").is_err());
+        assert!(Glob::new("/").is_err());
+        assert!(Glob::new("sprites/[ab.png").is_err());
+    }
+}
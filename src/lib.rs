@@ -29,6 +29,8 @@ pub fn run_cli(args: Vec<String>) {
     };
 
     if flags.dry_run { run_cli_dry(args, flags); }
+    else if flags.gen_user_event { run_cli_genue(args, flags); }
+    else if flags.edits_include() { run_cli_include(args, flags); }
     else if flags.is_silent { run_cli_silent(args, flags); } 
     else { run_cli_noisy(args, flags); }
 
@@ -51,10 +53,17 @@ fn run_cli_noisy(args: Vec<String>, flags: Flags) {
     }
 
     // Perform export
-    if let Err(e) = export_project(&args[1], &args[2], flags.user_event, flags.strip_whitespace, flags.strip_comments, flags.inert_run) {
-        eprintln!("Unexpected error while exporting project:");
-        eprintln!("    {e}");
-        process::exit(70);
+    match export_project(&args[1], &args[2], &flags.export_options()) {
+        Ok(warnings) => {
+            for w in warnings.iter() {
+                eprintln!("Warning: {w}");
+            }
+        }
+        Err(e) => {
+            eprintln!("Unexpected error while exporting project:");
+            eprintln!("    {e}");
+            process::exit(70);
+        }
     }
 
     // Apply export_config file
@@ -85,7 +94,7 @@ fn run_cli_silent(args: Vec<String>, flags: Flags) {
     }
 
     // Perform export
-    if let Err(_) = export_project(&args[1], &args[2], flags.user_event, flags.strip_whitespace, flags.strip_comments, flags.inert_run) {
+    if let Err(_) = export_project(&args[1], &args[2], &flags.export_options()) {
         process::exit(70);
     }
 
@@ -114,4 +123,62 @@ fn run_cli_dry(args: Vec<String>, flags: Flags) {
         }
         process::exit(70);
     }
-}
\ No newline at end of file
+}
+
+fn run_cli_genue(args: Vec<String>, flags: Flags) {
+    // Ensure validity of input
+    if let Err(e) = cli_check_source_valid(&args[1]) {
+        if !flags.is_silent {
+            eprintln!("Error with source directory:");
+            eprintln!("    {e}");
+        }
+        process::exit(65);
+    }
+
+    // Generate user_event from constants file
+    // (flags are validated to contain both of these)
+    let user_event = flags.user_event.unwrap();
+    let constants_file = flags.constants_file.as_deref().unwrap();
+    if let Err(e) = generate_user_event(&args[1], user_event, flags.user_event_path.as_deref(), constants_file) {
+        if !flags.is_silent {
+            eprintln!("Error while generating user_event:");
+            eprintln!("    {e}");
+        }
+        process::exit(72);
+    }
+
+    if !flags.is_silent {
+        println!("Generated user_event{} from {}.", user_event, constants_file);
+    }
+}
+fn run_cli_include(args: Vec<String>, flags: Flags) {
+    // Ensure validity of input
+    if let Err(e) = cli_check_source_valid(&args[1]) {
+        if !flags.is_silent {
+            eprintln!("Error with source directory:");
+            eprintln!("    {e}");
+        }
+        process::exit(65);
+    }
+
+    let entries = match edit_include(&args[1], &flags.include_add, &flags.include_remove) {
+        Ok(entries) => entries,
+        Err(e) => {
+            if !flags.is_silent {
+                eprintln!("Error while editing include file:");
+                eprintln!("    {e}");
+            }
+            process::exit(73);
+        }
+    };
+
+    if flags.is_silent { return };
+    if !flags.include_add.is_empty() || !flags.include_remove.is_empty() {
+        println!("Updated rcf_include.txt.");
+    }
+    if flags.include_list {
+        for e in entries.iter() {
+            println!("{e}");
+        }
+    }
+}
@@ -4,33 +4,87 @@ use crate::export_project::*;
 
 pub struct Flags {
     pub user_event: Option<u8>,
+    pub user_event_path: Option<String>,
+    pub constants_file: Option<String>,
     pub strip_comments: bool,
     pub strip_whitespace: bool,
+    pub prune_branches: bool,
     pub is_silent: bool,
     pub do_overwrite: bool,
     pub block_overwrite: bool,
     pub dry_run: bool,
     pub inert_run: bool,
+    pub ignore_case: bool,
+    pub gen_user_event: bool,
+    pub denied_functions: Vec<String>,
+    pub allowed_functions: Vec<String>,
+    pub profiles: Vec<String>,
+    pub receivers: Vec<String>,
+    pub include_add: Vec<String>,
+    pub include_remove: Vec<String>,
+    pub include_list: bool,
 }
 
 impl Flags {
     pub fn new() -> Flags {
         Flags {
             user_event: None,
+            user_event_path: None,
+            constants_file: None,
             strip_comments: false,
             strip_whitespace: false,
+            prune_branches: false,
             is_silent: false,
             do_overwrite: false,
             block_overwrite: false,
             dry_run: false,
             inert_run: false,
+            ignore_case: false,
+            gen_user_event: false,
+            denied_functions: Vec::new(),
+            allowed_functions: Vec::new(),
+            profiles: Vec::new(),
+            receivers: Vec::new(),
+            include_add: Vec::new(),
+            include_remove: Vec::new(),
+            include_list: false,
         }
     }
+
+    // Whether any of the include editing commands were given, which replace the export.
+    pub fn edits_include(&self) -> bool {
+        !self.include_add.is_empty() || !self.include_remove.is_empty() || self.include_list
+    }
+
+    pub fn export_options(&self) -> ExportOptions {
+        let mut options = ExportOptions::new();
+        options.user_event = self.user_event;
+        options.user_event_path = self.user_event_path.clone();
+        options.constants_file = self.constants_file.clone();
+        options.skip_whitespace = self.strip_whitespace;
+        options.skip_comments = self.strip_comments;
+        options.prune_branches = self.prune_branches;
+        options.inert_run = self.inert_run;
+        options.ignore_case = self.ignore_case;
+        options.runtime_functions.extend(self.denied_functions.iter().cloned());
+        options.runtime_functions.retain(|f| !self.allowed_functions.contains(f));
+        options.profiles = self.profiles.clone();
+        options.player_receivers.extend(self.receivers.iter().cloned());
+        options
+    }
 }
 
 pub fn get_flags(args: &Vec<String>) -> Result<Flags, String> {
     let mut f = Flags::new();
     let mut save_ue = false;
+    let mut save_ue_path = false;
+    let mut save_const = false;
+    let mut save_deny = false;
+    let mut save_allow = false;
+    let mut save_profile = false;
+    let mut save_receiver = false;
+    let mut save_incl_add = false;
+    let mut save_incl_rm = false;
     let mut passed_over = 0; // Used to ensure source and dest arguments are not flags
     for (idx, arg) in args.iter().enumerate() {
         if save_ue {
@@ -41,15 +95,59 @@ pub fn get_flags(args: &Vec<String>) -> Result<Flags, String> {
                 return Err(String::from("Provided user_event number is invalid"));
             }
         }
+        else if save_ue_path {
+            f.user_event_path = Some(arg.clone());
+            save_ue_path = false;
+        }
+        else if save_const {
+            f.constants_file = Some(arg.clone());
+            save_const = false;
+        }
+        else if save_deny {
+            f.denied_functions.extend(arg.split(',').map(|s| String::from(s.trim())));
+            save_deny = false;
+        }
+        else if save_allow {
+            f.allowed_functions.extend(arg.split(',').map(|s| String::from(s.trim())));
+            save_allow = false;
+        }
+        else if save_profile {
+            f.profiles.extend(arg.split(',').map(|s| String::from(s.trim())));
+            save_profile = false;
+        }
+        else if save_incl_add {
+            f.include_add.push(arg.clone());
+            save_incl_add = false;
+        }
+        else if save_incl_rm {
+            f.include_remove.push(arg.clone());
+            save_incl_rm = false;
+        }
+        else if save_receiver {
+            f.receivers.extend(arg.split(',').map(|s| String::from(s.trim())));
+            save_receiver = false;
+        }
         else { match arg.as_str() {
             "-ue" => save_ue = true,
+            "-uepath" => save_ue_path = true,
+            "-const" => save_const = true,
+            "-genue" => f.gen_user_event = true,
+            "-deny" => save_deny = true,
+            "-allow" => save_allow = true,
+            "-profile" => save_profile = true,
+            "-receiver" => save_receiver = true,
             "-c" => f.strip_comments = true,
             "-w" => f.strip_whitespace = true,
+            "-prune" => f.prune_branches = true,
             "-s" => f.is_silent = true,
             "-o" => f.do_overwrite = true,
             "-safe" => f.block_overwrite = true,
             "-init" => f.dry_run = true,
             "-inert" => f.inert_run = true,
+            "-nocase" => f.ignore_case = true,
+            "-incladd" => save_incl_add = true,
+            "-inclrm" => save_incl_rm = true,
+            "-incllist" => f.include_list = true,
             _ => if idx <= 2 {
                 passed_over += 1;
             },
@@ -59,7 +157,25 @@ pub fn get_flags(args: &Vec<String>) -> Result<Flags, String> {
     if save_ue {
         Err(String::from("Flag -ue must be followed by a user_event number"))
     }
-    else if passed_over < 2 || (passed_over == 2 && !f.dry_run) {
+    else if save_ue_path {
+        Err(String::from("Flag -uepath must be followed by a file path"))
+    }
+    else if save_const {
+        Err(String::from("Flag -const must be followed by a file path"))
+    }
+    else if save_deny || save_allow {
+        Err(String::from("Flags -deny and -allow must be followed by a list of function names"))
+    }
+    else if save_profile {
+        Err(String::from("Flag -profile must be followed by a list of profile names"))
+    }
+    else if save_receiver {
+        Err(String::from("Flag -receiver must be followed by a list of accessor chains"))
+    }
+    else if save_incl_add || save_incl_rm {
+        Err(String::from("Flags -incladd and -inclrm must be followed by an include entry"))
+    }
+    else if passed_over < 2 || (passed_over == 2 && !f.dry_run && !f.gen_user_event && !f.edits_include()) {
         Err(String::from("Source and destination arguments must not be flags"))
     }
     else if f.do_overwrite && f.block_overwrite {
@@ -71,6 +187,18 @@ pub fn get_flags(args: &Vec<String>) -> Result<Flags, String> {
     else if f.dry_run && f.inert_run {
         Err(String::from("Flags -init and -inert are mutually exclusive"))
     }
+    else if f.user_event_path.is_some() && f.user_event.is_none() {
+        Err(String::from("Flag -uepath must be used alongside flag -ue"))
+    }
+    else if f.gen_user_event && (f.user_event.is_none() || f.constants_file.is_none()) {
+        Err(String::from("Flag -genue must be used alongside flags -ue and -const"))
+    }
+    else if f.gen_user_event && (f.dry_run || f.inert_run) {
+        Err(String::from("Flag -genue is incompatible with flags -init and -inert"))
+    }
+    else if f.edits_include() && (f.dry_run || f.inert_run || f.gen_user_event) {
+        Err(String::from("Flags -incladd, -inclrm and -incllist are incompatible with flags -init, -inert and -genue"))
+    }
     else {
         Ok(f)
     }
@@ -80,12 +208,24 @@ pub fn get_flags(args: &Vec<String>) -> Result<Flags, String> {
 pub fn cli_print_help() {
     println!("Usage: rcf.exe [source directory] [destination directory] [flags]");
     println!("-ue [#]: Sets the user_event used as the constant source");
+    println!("-uepath [path]: Sets the location of the -ue user_event (default: scripts/user_event#.gml)");
+    println!("-const [file]: Reads additional constants from a .csv, .json or .toml file");
+    println!("-genue: Generates the -ue user_event from the -const file, but does not export");
+    println!("-deny [f1,f2,...]: Adds functions that constants may not call");
+    println!("-allow [f1,f2,...]: Removes functions from the list that constants may not call");
+    println!("-profile [p1,p2,...]: Enables profiles for //#RCFIF conditions");
+    println!("-receiver [r1,r2,...]: Adds accessor chains (e.g. other.orig_player_id) that refer to this character");
     println!("-c: Strip comments on export");
     println!("-w: Strip whitespace on export");
+    println!("-prune: Remove if/else branches whose conditions are constant on export");
     println!("-s: Silent mode (mutes output; requires -o or -safe)");
     println!("-o: Force overwrite of destination directory (may result in data loss)");
     println!("-safe: Disable overwrite of destination directory (incompatible with -o)");
     println!("-init: Initializes RCF files in the source, but does not export");
+    println!("-nocase: Ignore letter case when matching rcf_include.txt entries");
+    println!("-incladd [entry]: Adds an entry to rcf_include.txt, but does not export");
+    println!("-inclrm [entry]: Removes an entry from rcf_include.txt, but does not export");
+    println!("-incllist: Lists the entries in rcf_include.txt, but does not export");
     println!("-inert: Exports as normal, but does not initialize RCF files in the source (incompatible with -init)");
 }
 